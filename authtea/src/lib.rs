@@ -1,61 +1,93 @@
+#![no_std]
+#![forbid(unsafe_code)]
 #![allow(clippy::many_single_char_names)]
 
-use std::{mem::size_of, ptr::copy_nonoverlapping};
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+const DELTA: u32 = 0x9E3779B9;
 
 pub struct AuthTea {
     k: [u32; 4],
 }
 
-fn to_u32_with_len(a: &[u8]) -> Vec<u8> {
-    let c = a.len();
-    let n = (c + 3) / 4;
-    let mut v = vec![0; (n + 1) * 4];
-    let pv = v.as_mut_ptr();
-    unsafe {
-        pv.cast::<u32>().add(n).write_unaligned(c as u32);
-        copy_nonoverlapping(a.as_ptr(), pv, c);
-    }
-    v
+fn to_words(a: &[u8]) -> Vec<u32> {
+    a.chunks(4)
+        .map(|c| {
+            let mut w = [0; 4];
+            w[..c.len()].copy_from_slice(c);
+            u32::from_le_bytes(w)
+        })
+        .collect()
+}
+
+fn to_bytes(v: &[u32]) -> Vec<u8> {
+    v.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+fn rounds(n: usize) -> usize {
+    6 + 52 / (n + 1)
 }
 
 impl AuthTea {
     pub fn new(key: &[u8]) -> Self {
         let mut k = [0; 4];
-        if !key.is_empty() {
-            unsafe {
-                copy_nonoverlapping(
-                    key.as_ptr(),
-                    k.as_mut_ptr().cast(),
-                    key.len().min(size_of::<[u32; 4]>()),
-                );
-            }
+        for (k, w) in k.iter_mut().zip(to_words(&key[..key.len().min(16)])) {
+            *k = w;
         }
         Self { k }
     }
 
+    fn mx(&self, d: u32, p: usize, y: u32, z: u32) -> u32 {
+        let e = (d >> 2) & 3;
+        ((z >> 5) ^ (y << 2))
+            .wrapping_add((y >> 3) ^ (z << 4) ^ (d ^ y))
+            .wrapping_add(self.k[(((p & 3) as u32) ^ e) as usize] ^ z)
+    }
+
     pub fn encode(&self, data: &[u8]) -> Vec<u8> {
-        let mut vv = to_u32_with_len(data);
-        let n = vv.len() / 4 - 1;
-        let v = vv.as_mut_ptr().cast::<u32>();
-        let mut y: u32;
-        let q = 6 + 52 / (n + 1);
+        let mut v = to_words(data);
+        v.push(data.len() as u32);
+        let n = v.len() - 1;
         let mut d: u32 = 0;
-        unsafe {
-            let mut z = v.add(n).read_unaligned();
-            for _i in 0..q {
-                d = d.wrapping_add(0x9E3779B9);
-                let e = (d >> 2) & 3;
-                for p in 0..=n {
-                    y = v.add((p + 1) % (n + 1)).read_unaligned();
-                    let mut m = (z >> 5) ^ (y << 2);
-                    m = m.wrapping_add((y >> 3) ^ (z << 4) ^ (d ^ y));
-                    m = m.wrapping_add(self.k[(((p & 3) as u32) ^ e) as usize] ^ z);
-                    m = m.wrapping_add(v.add(p).read_unaligned());
-                    v.add(p).write_unaligned(m);
-                    z = m;
-                }
+        let mut z = v[n];
+        for _i in 0..rounds(n) {
+            d = d.wrapping_add(DELTA);
+            for p in 0..=n {
+                let y = v[(p + 1) % (n + 1)];
+                v[p] = v[p].wrapping_add(self.mx(d, p, y, z));
+                z = v[p];
+            }
+        }
+        to_bytes(&v)
+    }
+
+    /// The inverse of [`AuthTea::encode`].
+    ///
+    /// Returns `None` if the data is not a valid encoded message.
+    pub fn decode(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < 8 || data.len() & 3 != 0 {
+            return None;
+        }
+        let mut v = to_words(data);
+        let n = v.len() - 1;
+        let q = rounds(n);
+        let mut d = (q as u32).wrapping_mul(DELTA);
+        for _i in 0..q {
+            for p in (0..=n).rev() {
+                let y = v[(p + 1) % (n + 1)];
+                let z = v[if p == 0 { n } else { p - 1 }];
+                v[p] = v[p].wrapping_sub(self.mx(d, p, y, z));
             }
+            d = d.wrapping_sub(DELTA);
+        }
+        let len = v[n] as usize;
+        if len > n * 4 || len + 4 <= n * 4 {
+            return None;
         }
-        vv
+        let mut res = to_bytes(&v[..n]);
+        res.truncate(len);
+        Some(res)
     }
 }
@@ -0,0 +1,45 @@
+use authtea::AuthTea;
+
+// Generated with xEncode from the srun portal JS.
+static VECTORS: &[(&str, &str, &str)] = &[
+    ("k", "a", "10d188dc61522d85"),
+    ("0123456789abcdef", "tunet", "acae910467694d878beb31af"),
+    (
+        "secret",
+        "hello, world!",
+        "daa8d4d59120987ee7496ff5736782e49ffde789",
+    ),
+    (
+        "8a6ab1b7ac2b3bb2a58ff7ba42c2c5e33c34c5bb8b1b02d5bc89acc5de2a3d2b",
+        r#"{"username":"user","password":"pass","ip":"","acid":"1","enc_ver":"srun_bx1"}"#,
+        "f6fcd545c8b00ebf82a8a0a3adeb44311bffac5bdbc9221db13fcc08b7f5917511b98071f80c5ef23311a061d6b4af198058a3ce4566168f6520772136c6e8d683fe8af22181d97638cfd771807dabcedb979ddd",
+    ),
+];
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn encode() {
+    for (key, data, expected) in VECTORS {
+        let tea = AuthTea::new(key.as_bytes());
+        assert_eq!(hex(&tea.encode(data.as_bytes())), *expected);
+    }
+}
+
+#[test]
+fn decode() {
+    for (key, data, _) in VECTORS {
+        let tea = AuthTea::new(key.as_bytes());
+        let encoded = tea.encode(data.as_bytes());
+        assert_eq!(tea.decode(&encoded).as_deref(), Some(data.as_bytes()));
+    }
+}
+
+#[test]
+fn decode_invalid() {
+    let tea = AuthTea::new(b"key");
+    assert_eq!(tea.decode(b"short"), None);
+    assert_eq!(tea.decode(&[0; 16]), None);
+}
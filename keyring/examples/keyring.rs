@@ -0,0 +1,18 @@
+use anyhow::{anyhow, Result};
+use keyring::Keyring;
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (cmd, key) = match (args.next(), args.next()) {
+        (Some(cmd), Some(key)) => (cmd, key),
+        _ => return Err(anyhow!("Usage: keyring <get|set|delete> <key> [value]")),
+    };
+    let mut keyring = Keyring::new(&key)?;
+    match cmd.as_str() {
+        "get" => println!("{}", keyring.get()?),
+        "set" => keyring.set(&args.next().ok_or_else(|| anyhow!("Missing value."))?)?,
+        "delete" => keyring.delete()?,
+        _ => return Err(anyhow!("Unknown command {}.", cmd)),
+    }
+    Ok(())
+}
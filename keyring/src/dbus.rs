@@ -0,0 +1,53 @@
+use anyhow::{anyhow, Result};
+use secret_service::{blocking::SecretService, EncryptionType};
+use std::collections::HashMap;
+
+static APPLICATION: &str = "tunet-rust";
+
+pub struct Keyring {
+    service: SecretService<'static>,
+}
+
+impl Keyring {
+    pub fn new() -> Result<Self> {
+        let service = SecretService::connect(EncryptionType::Dh)?;
+        service.get_default_collection()?;
+        Ok(Self { service })
+    }
+
+    fn attributes(key: &str) -> HashMap<&str, &str> {
+        HashMap::from([("application", APPLICATION), ("key", key)])
+    }
+
+    pub fn get(&self, key: &str) -> Result<String> {
+        let collection = self.service.get_default_collection()?;
+        collection.ensure_unlocked()?;
+        let items = collection.search_items(Self::attributes(key))?;
+        let item = items
+            .first()
+            .ok_or_else(|| anyhow!("Keyring: cannot find the secret."))?;
+        Ok(String::from_utf8(item.get_secret()?)?)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let collection = self.service.get_default_collection()?;
+        collection.ensure_unlocked()?;
+        collection.create_item(
+            &format!("{} ({})", APPLICATION, key),
+            Self::attributes(key),
+            value.as_bytes(),
+            true,
+            "text/plain",
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: &str) -> Result<()> {
+        let collection = self.service.get_default_collection()?;
+        collection.ensure_unlocked()?;
+        for item in collection.search_items(Self::attributes(key))? {
+            item.delete()?;
+        }
+        Ok(())
+    }
+}
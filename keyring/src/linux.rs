@@ -1,33 +1,99 @@
-use keyutils::{keytypes::User, Keyring as LinuxKeyring, Result, SpecialKeyring};
+use anyhow::{anyhow, Result};
+use keyutils::{keytypes::User, Keyring as LinuxKeyring, SpecialKeyring};
+use std::str::FromStr;
+
+#[path = "dbus.rs"]
+mod dbus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyringBackend {
+    SecretService,
+    Keyutils,
+}
+
+impl FromStr for KeyringBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "secret-service" => Ok(Self::SecretService),
+            "keyutils" => Ok(Self::Keyutils),
+            _ => Err(anyhow!("Keyring: unknown backend {}.", s)),
+        }
+    }
+}
+
+pub static KEYRING_BACKEND_ENV: &str = "TUNET_KEYRING";
+
+enum Backend {
+    SecretService(dbus::Keyring),
+    Keyutils(LinuxKeyring),
+}
 
 pub struct Keyring {
-    keyring: LinuxKeyring,
+    backend: Backend,
     key: String,
 }
 
 impl Keyring {
     pub fn new(key: &str) -> Result<Self> {
+        match std::env::var(KEYRING_BACKEND_ENV) {
+            Ok(name) => Self::with_backend(key, name.parse()?),
+            Err(_) => Self::with_backend(key, KeyringBackend::SecretService)
+                .or_else(|_| Self::with_backend(key, KeyringBackend::Keyutils)),
+        }
+    }
+
+    pub fn with_backend(key: &str, backend: KeyringBackend) -> Result<Self> {
+        let backend = match backend {
+            KeyringBackend::SecretService => Backend::SecretService(dbus::Keyring::new()?),
+            KeyringBackend::Keyutils => Backend::Keyutils(unsafe {
+                LinuxKeyring::new(SpecialKeyring::UserSession.serial())
+            }),
+        };
         Ok(Self {
-            keyring: unsafe { LinuxKeyring::new(SpecialKeyring::UserSession.serial()) },
+            backend,
             key: key.to_owned(),
         })
     }
 
+    pub fn backend(&self) -> KeyringBackend {
+        match self.backend {
+            Backend::SecretService(_) => KeyringBackend::SecretService,
+            Backend::Keyutils(_) => KeyringBackend::Keyutils,
+        }
+    }
+
     pub fn get(&self) -> Result<String> {
-        let key = self
-            .keyring
-            .search_for_key::<User, _, _>(self.key.as_str(), None)?;
-        let value = key.read()?;
-        Ok(unsafe { String::from_utf8_unchecked(value) })
+        match &self.backend {
+            Backend::SecretService(keyring) => keyring.get(&self.key),
+            Backend::Keyutils(keyring) => {
+                let key = keyring.search_for_key::<User, _, _>(self.key.as_str(), None)?;
+                let value = key.read()?;
+                Ok(String::from_utf8(value)?)
+            }
+        }
     }
 
     pub fn set(&mut self, value: &str) -> Result<()> {
-        self.keyring
-            .add_key::<User, _, _>(self.key.as_str(), value.as_bytes())?;
-        Ok(())
+        match &mut self.backend {
+            Backend::SecretService(keyring) => keyring.set(&self.key, value),
+            Backend::Keyutils(keyring) => {
+                keyring.add_key::<User, _, _>(self.key.as_str(), value.as_bytes())?;
+                Ok(())
+            }
+        }
     }
 
     pub fn delete(&mut self) -> Result<()> {
-        self.keyring.clear()
+        match &mut self.backend {
+            Backend::SecretService(keyring) => keyring.delete(&self.key),
+            Backend::Keyutils(keyring) => {
+                // Only unlink our own key; clearing would wipe the whole session keyring.
+                let key = keyring.search_for_key::<User, _, _>(self.key.as_str(), None)?;
+                keyring.unlink_key(&key)?;
+                Ok(())
+            }
+        }
     }
 }
@@ -22,12 +22,12 @@ impl Keyring {
         Ok(String::from_utf8_lossy(&password_bytes).into_owned())
     }
 
-    pub fn set(&self, value: &str) -> Result<()> {
+    pub fn set(&mut self, value: &str) -> Result<()> {
         self.keychain
             .set_generic_password(&self.key, TUNET_DUMMY_USERNAME, value.as_bytes())
     }
 
-    pub fn delete(&self) -> Result<()> {
+    pub fn delete(&mut self) -> Result<()> {
         let (_, item) = self
             .keychain
             .find_generic_password(&self.key, TUNET_DUMMY_USERNAME)?;
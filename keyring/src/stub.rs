@@ -11,11 +11,11 @@ impl Keyring {
         Err(anyhow!("Keyring: unsupported platform."))
     }
 
-    pub fn set(&self, _value: &str) -> Result<String> {
+    pub fn set(&mut self, _value: &str) -> Result<()> {
         Err(anyhow!("Keyring: unsupported platform."))
     }
 
-    pub fn delete(&self) -> Result<()> {
+    pub fn delete(&mut self) -> Result<()> {
         Err(anyhow!("Keyring: unsupported platform."))
     }
 }
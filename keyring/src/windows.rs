@@ -39,7 +39,7 @@ impl Keyring {
         }
     }
 
-    pub fn set(&self, value: &str) -> Result<()> {
+    pub fn set(&mut self, value: &str) -> Result<()> {
         unsafe {
             let value = value.as_bytes();
             let credential = CREDENTIALW {
@@ -60,7 +60,7 @@ impl Keyring {
         }
     }
 
-    pub fn delete(&self) -> Result<()> {
+    pub fn delete(&mut self) -> Result<()> {
         unsafe { CredDeleteW(pcwstr!(self.key), CRED_TYPE_GENERIC.0, 0).ok() }
     }
 }
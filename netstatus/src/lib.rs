@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
 
 cfg_if::cfg_if! {
     if #[cfg(target_os = "windows")] {
@@ -28,6 +29,33 @@ impl NetStatus {
     pub fn current() -> Self {
         platform::current()
     }
+
+    /// 标识当前网络：无线网络使用 SSID，其他情况使用默认网关。
+    pub fn network(&self) -> Option<String> {
+        match self {
+            Self::Wlan(ssid) => Some(format!("ssid:{}", ssid)),
+            _ => gateway().map(|g| format!("gateway:{}", g)),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn gateway() -> Option<IpAddr> {
+    let route = std::fs::read_to_string("/proc/net/route").ok()?;
+    route
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| fields.len() > 2 && fields[1] == "00000000")
+        .find_map(|fields| u32::from_str_radix(fields[2], 16).ok())
+        .filter(|g| *g != 0)
+        // 内核按本机字节序输出网络字节序的地址。
+        .map(|g| IpAddr::from(g.to_ne_bytes()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn gateway() -> Option<IpAddr> {
+    None
 }
 
 impl Display for NetStatus {
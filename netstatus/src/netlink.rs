@@ -19,9 +19,7 @@ fn current_impl() -> Result<NetStatus> {
 
 pub fn current() -> NetStatus {
     current_impl().unwrap_or_else(|e| {
-        if cfg!(debug_assertions) {
-            eprintln!("WARNING: {}", e);
-        }
+        tracing::warn!("无法获取网络状态：{}", e);
         NetStatus::Unknown
     })
 }
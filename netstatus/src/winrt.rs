@@ -18,9 +18,7 @@ fn current_impl() -> Result<NetStatus> {
 
 pub fn current() -> NetStatus {
     current_impl().unwrap_or_else(|e| {
-        if cfg!(debug_assertions) {
-            eprintln!("WARNING: {}", e.message());
-        }
+        tracing::warn!("无法获取网络状态：{}", e.message());
         NetStatus::Unknown
     })
 }
@@ -0,0 +1,178 @@
+#![no_std]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use authtea::AuthTea;
+use core::fmt::Write;
+use data_encoding::{Encoding, Specification, SpecificationError, HEXLOWER};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use zeroize::Zeroize;
+
+pub use data_encoding;
+
+pub static DEFAULT_ALPHABET: &str =
+    "LVoJPiCN2R8G90yg+hmFHuacZ1OWMnrsSTXkYpUq/3dlbfKwv6xztjI7DeBE45QA";
+
+static INFO_PREFIX: &str = "{SRBX1}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChksumField {
+    Username,
+    Hmd5,
+    AcId,
+    Ip,
+    N,
+    Type,
+    Info,
+}
+
+pub static DEFAULT_CHKSUM: &[ChksumField] = &[
+    ChksumField::Username,
+    ChksumField::Hmd5,
+    ChksumField::AcId,
+    ChksumField::Ip,
+    ChksumField::N,
+    ChksumField::Type,
+    ChksumField::Info,
+];
+
+#[derive(Debug)]
+pub enum Error {
+    Alphabet(SpecificationError),
+    Base64(data_encoding::DecodeError),
+    Token,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Alphabet(e) => write!(f, "无效的编码字母表：{}", e),
+            Self::Base64(e) => write!(f, "无法解码 info：{}", e),
+            Self::Token => f.write_str("无法解码 info：token 不匹配"),
+        }
+    }
+}
+
+pub fn encoding(alphabet: &str) -> Result<Encoding, Error> {
+    let mut spec = Specification::new();
+    spec.symbols.push_str(alphabet);
+    spec.padding = Some('=');
+    spec.encoding().map_err(Error::Alphabet)
+}
+
+/// HMAC-MD5 of the challenge with an empty key, used as the password hash.
+pub fn hmd5(token: &str) -> String {
+    let mut hmacmd5 = Hmac::<Md5>::new_from_slice(&[]).unwrap();
+    hmacmd5.update(token.as_bytes());
+    HEXLOWER.encode(&hmacmd5.finalize().into_bytes())
+}
+
+fn write_json_str(buf: &mut String, s: &str) {
+    buf.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            '\u{08}' => buf.push_str("\\b"),
+            '\u{0c}' => buf.push_str("\\f"),
+            c if (c as u32) < 0x20 => {
+                write!(buf, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => buf.push(c),
+        }
+    }
+    buf.push('"');
+}
+
+/// The fields of a login request.
+pub struct Login<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+    pub ip: &'a str,
+    pub ac_id: i32,
+    pub enc_ver: &'a str,
+    pub n: &'a str,
+    pub ty: &'a str,
+}
+
+/// The computed parameters of a login request.
+pub struct LoginParams {
+    pub password: String,
+    pub info: String,
+    pub chksum: String,
+}
+
+impl Login<'_> {
+    fn info_json(&self) -> String {
+        let mut buf = String::new();
+        buf.push_str("{\"username\":");
+        write_json_str(&mut buf, self.username);
+        buf.push_str(",\"password\":");
+        write_json_str(&mut buf, self.password);
+        buf.push_str(",\"ip\":");
+        write_json_str(&mut buf, self.ip);
+        write!(buf, ",\"acid\":{},\"enc_ver\":", self.ac_id).unwrap();
+        write_json_str(&mut buf, self.enc_ver);
+        buf.push('}');
+        buf
+    }
+
+    pub fn info(&self, token: &str, encoding: &Encoding) -> String {
+        let mut json = self.info_json();
+        let info = AuthTea::new(token.as_bytes()).encode(json.as_bytes());
+        json.zeroize();
+        format!("{}{}", INFO_PREFIX, encoding.encode(&info))
+    }
+
+    pub fn params(&self, token: &str, encoding: &Encoding, chksum: &[ChksumField]) -> LoginParams {
+        let hmd5 = hmd5(token);
+        let info = self.info(token, encoding);
+        let ac_id = self.ac_id.to_string();
+        let mut sha1 = Sha1::new();
+        for field in chksum {
+            sha1.update(token);
+            sha1.update(match field {
+                ChksumField::Username => self.username,
+                ChksumField::Hmd5 => &hmd5,
+                ChksumField::AcId => &ac_id,
+                ChksumField::Ip => self.ip,
+                ChksumField::N => self.n,
+                ChksumField::Type => self.ty,
+                ChksumField::Info => &info,
+            });
+        }
+        LoginParams {
+            password: format!("{{MD5}}{}", hmd5),
+            info,
+            chksum: HEXLOWER.encode(&sha1.finalize()),
+        }
+    }
+}
+
+/// Reverses [`Login::info`].
+pub fn decode_info(info: &str, token: &str, encoding: &Encoding) -> Result<Vec<u8>, Error> {
+    let info = info.trim();
+    let data = info.strip_prefix(INFO_PREFIX).unwrap_or(info);
+    let data = encoding.decode(data.as_bytes()).map_err(Error::Base64)?;
+    AuthTea::new(token.as_bytes())
+        .decode(&data)
+        .ok_or(Error::Token)
+}
+
+/// The signature of a `rad_user_dm` request.
+pub fn drop_sign(time: &str, username: &str, ip: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(format!("{0}{1}{2}1{0}", time, username, ip));
+    HEXLOWER.encode(&sha1.finalize())
+}
@@ -10,6 +10,7 @@ use tokio::sync::mpsc::*;
 use tui::layout::Rect;
 use tunet_helper::*;
 use tunet_model::*;
+use tunet_settings_cli::FileSettingsReader;
 
 #[derive(Debug)]
 pub enum EventType {
@@ -104,6 +105,23 @@ impl Event {
         self.model.queue(Action::Details);
     }
 
+    pub fn spawn_profile(&self) {
+        let profiles = std::iter::once(None)
+            .chain(
+                FileSettingsReader::profiles()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Some),
+            )
+            .collect::<Vec<_>>();
+        let next = profiles
+            .iter()
+            .position(|p| *p == self.model.profile)
+            .map(|i| (i + 1) % profiles.len())
+            .unwrap_or_default();
+        self.model.queue(Action::Profile(profiles[next].clone()));
+    }
+
     pub fn handle(&mut self, e: EventType, rect: Rect) -> bool {
         match e {
             EventType::TerminalEvent(e) => match e {
@@ -144,6 +162,7 @@ impl Event {
             4 => self.spawn_online(),
             5 => self.spawn_details(),
             6 => return false,
+            7 => self.spawn_profile(),
             _ => {}
         };
         true
@@ -7,10 +7,11 @@ use crossterm::{
     terminal::*,
 };
 use futures_util::TryStreamExt;
+use std::path::PathBuf;
 use tokio::runtime::Builder as RuntimeBuilder;
 use tui::{backend::CrosstermBackend, layout::*, text::*, widgets::*, Terminal};
-use tunet_helper::*;
-use tunet_model::Action;
+use tunet_helper::{billing::*, *};
+use tunet_model::{Action, Hook};
 use tunet_settings_cli::*;
 
 mod event;
@@ -24,21 +25,78 @@ struct Opt {
     #[clap(long, short = 's')]
     /// 连接方式
     host: Option<NetState>,
+    #[clap(long = "tier")]
+    /// 计费档位，如 20G:1.0
+    tiers: Vec<TariffTier>,
+    #[clap(long)]
+    /// 每月费用上限
+    cap: Option<f64>,
+    #[clap(long = "alert")]
+    /// 流量提醒阈值，如 15G
+    flux_alerts: Vec<Flux>,
+    #[clap(long)]
+    /// 余额提醒阈值
+    balance_alert: Option<f64>,
+    #[clap(long)]
+    /// 使用的配置名称
+    profile: Option<String>,
+    #[clap(long, conflicts_with = "profile")]
+    /// 使用的设置文件路径，不能与 --profile 同时使用
+    config: Option<PathBuf>,
+    #[clap(long = "hook")]
+    /// 事件钩子，如 login=/path/to/script
+    hooks: Vec<Hook>,
+    #[clap(short, long, parse(from_occurrences))]
+    /// 输出更详细的日志，可重复使用；未指定 --log-file 时写入配置目录下的 tunet-cui.log
+    verbose: u8,
+    #[clap(long)]
+    /// 日志文件路径，界面运行时日志只会写入该文件
+    log_file: Option<PathBuf>,
+}
+
+impl Opt {
+    fn apply(&self, model: &mut tunet_model::Model) {
+        model.tariff = std::mem::take(&mut model.tariff).with_overrides(&self.tiers, self.cap);
+        if !self.flux_alerts.is_empty() {
+            model.thresholds.flux = self.flux_alerts.clone();
+        }
+        if let Some(b) = self.balance_alert {
+            model.thresholds.balance = Some(Balance(b));
+        }
+        if !self.hooks.is_empty() {
+            model.hooks = self.hooks.clone();
+        }
+    }
 }
 
 fn main() -> Result<()> {
     let opt = Opt::parse();
+    // 界面占用终端，日志只能写入文件。
+    let log_file = match &opt.log_file {
+        Some(path) => Some(path.clone()),
+        None if opt.verbose > 0 => Some(FileSettingsReader::data_path("tunet-cui.log")?),
+        None => None,
+    };
+    if let Some(log_file) = &log_file {
+        init_logging(opt.verbose, Some(log_file))?;
+    }
+    FileSettingsReader::set_default(opt.profile.clone(), opt.config.clone());
     RuntimeBuilder::new_multi_thread()
         .enable_all()
         .build()?
-        .block_on(run(opt.host))
+        .block_on(run(opt))
 }
 
-pub async fn run(state: Option<NetState>) -> Result<()> {
+async fn run(opt: Opt) -> Result<()> {
     let mut event = Event::new()?;
+    event.model.load_preferences(&read_preferences())?;
+    opt.apply(&mut event.model);
+    event.model.notify = true;
 
     event.model.queue(Action::Credential(read_cred()?));
-    event.model.queue(Action::State(state));
+    event
+        .model
+        .queue(Action::State(opt.host.or_else(read_host)));
 
     enable_raw_mode()?;
     execute!(std::io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
@@ -46,7 +104,9 @@ pub async fn run(state: Option<NetState>) -> Result<()> {
     let res = main_loop(&mut event).await;
 
     let res = if let Ok(()) = res {
-        save_cred(event.model.cred.clone()).await
+        FileSettingsReader::with_profile(event.model.profile.as_deref())?
+            .save(event.model.cred.clone())
+            .await
     } else {
         res
     };
@@ -26,11 +26,13 @@ pub fn draw<B: Backend>(m: &Model, f: &mut Frame<B>) {
         .split(global_chunks[0]);
     let title_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(6), Constraint::Percentage(100)])
+        .constraints([Constraint::Length(8), Constraint::Percentage(100)])
         .split(chunks[0]);
 
     let subtitle_style = Style::default().fg(Color::Cyan);
 
+    let estimate = m.estimate();
+
     let graph = {
         Paragraph::new(vec![
             Spans::from(vec![
@@ -65,6 +67,25 @@ pub fn draw<B: Backend>(m: &Model, f: &mut Frame<B>) {
                     Style::default().fg(Color::Yellow),
                 ),
             ]),
+            Spans::from(vec![
+                Span::styled("费用 ", subtitle_style),
+                Span::styled(
+                    estimate.charge.to_string(),
+                    Style::default().fg(Color::Yellow),
+                ),
+            ]),
+            Spans::from(vec![
+                Span::styled("预计 ", subtitle_style),
+                Span::styled(
+                    estimate.projected_flux.to_string(),
+                    Style::default().fg(get_flux_color(estimate.projected_flux.0, true)),
+                ),
+                Span::raw(" "),
+                Span::styled(
+                    estimate.projected_charge.to_string(),
+                    Style::default().fg(Color::Yellow),
+                ),
+            ]),
         ])
     }
     .block(Block::default().title("基础信息").borders(Borders::all()));
@@ -86,7 +107,10 @@ pub fn draw<B: Backend>(m: &Model, f: &mut Frame<B>) {
                     ]),
                     Spans::from(vec![
                         Span::styled("登录时间 ", subtitle_style),
-                        Span::styled(u.login_time.to_string(), Style::default().fg(Color::Green)),
+                        Span::styled(
+                            u.login_time.naive_local().to_string(),
+                            Style::default().fg(Color::Green),
+                        ),
                     ]),
                     Spans::from(vec![
                         Span::styled("流量     ", subtitle_style),
@@ -120,7 +144,7 @@ pub fn draw<B: Backend>(m: &Model, f: &mut Frame<B>) {
     .block(Block::default().title("连接详情").borders(Borders::all()));
     f.render_widget(table, title_chunks[1]);
 
-    let now = Local::now();
+    let now = campus_now();
 
     let mut max = Flux(0);
     let mut details = vec![];
@@ -128,7 +152,7 @@ pub fn draw<B: Backend>(m: &Model, f: &mut Frame<B>) {
     let details_group = m
         .details
         .iter()
-        .group_by(|d| d.logout_time.date())
+        .group_by(|d| d.logout_time.naive_local().date())
         .into_iter()
         .map(|(key, group)| (key.day(), group.map(|d| d.flux.0).sum::<u64>()))
         .collect::<HashMap<_, _>>();
@@ -183,6 +207,8 @@ pub fn draw<B: Backend>(m: &Model, f: &mut Frame<B>) {
         Span::raw("刷新图表"),
         Span::styled("F6", key_style),
         Span::raw("退出    "),
+        Span::styled("F7", key_style),
+        Span::raw("切换配置"),
     ];
 
     let log = &m.log;
@@ -190,6 +216,10 @@ pub fn draw<B: Backend>(m: &Model, f: &mut Frame<B>) {
         spans.push(Span::styled("  ", key_style));
         spans.push(Span::raw(log.as_ref()));
     }
+    if !m.alerts.is_empty() {
+        spans.push(Span::styled("  ", key_style));
+        spans.push(Span::raw(m.alerts.iter().map(|a| a.to_string()).join("，")));
+    }
     if m.online_busy() {
         spans.push(Span::styled("  ", key_style));
         spans.push(Span::raw("正在刷新在线"));
@@ -4,6 +4,7 @@
 #include <QDateTime>
 #include <QObject>
 #include <QString>
+#include <QStringList>
 #include <algorithm>
 #include <array>
 #include <chrono>
@@ -37,6 +38,8 @@ namespace TUNet
         LogBusy,
         OnlineBusy,
         DetailBusy,
+        Alert,
+        Profile,
     };
 
     enum class State : std::int32_t
@@ -47,6 +50,15 @@ namespace TUNet
         Auth6,
     };
 
+    enum class LogLevel : std::int32_t
+    {
+        Error,
+        Warn,
+        Info,
+        Debug,
+        Trace,
+    };
+
     using NativeModel = const void*;
 
     struct Credential
@@ -100,6 +112,13 @@ namespace TUNet
         double balance;
     };
 
+    struct Estimate
+    {
+        double charge;
+        Flux projected_flux;
+        double projected_charge;
+    };
+
     struct Online
     {
         Ipv4Addr address;
@@ -137,6 +156,10 @@ namespace TUNet
         QColor accent_color() const;
         QString log() const;
         Info flux() const;
+        Estimate estimate() const;
+        QString alerts() const;
+        QString profile() const;
+        static QStringList profiles();
         std::vector<Online> onlines() const;
         std::vector<Detail> details() const;
         std::map<QDate, Flux> details_grouped() const;
@@ -150,6 +173,7 @@ namespace TUNet
         void queue_cred_load() const;
         void queue_cred(const Credential& cred) const;
         void queue_state(State s) const;
+        void queue_profile(const QString& profile) const;
         void queue_connect(Ipv4Addr addr) const;
         void queue_drop(Ipv4Addr addr) const;
         void update(UpdateMsg m) const;
@@ -170,6 +194,9 @@ namespace TUNet
         void online_busy_changed() const;
         void detail_busy_changed() const;
 
+        void alerts_changed() const;
+        void profile_changed() const;
+
     private:
         NativeModel m_handle{};
     };
@@ -1,4 +1,5 @@
 #include <Model.hpp>
+#include <QDebug>
 
 #if QT_VERSION < QT_VERSION_CHECK(5, 10, 0)
     #define QSTRING_UTF16(str) (reinterpret_cast<const char16_t*>((str).utf16()))
@@ -11,6 +12,7 @@
 extern "C"
 {
     using TUNet::Action;
+    using TUNet::LogLevel;
     using TUNet::NativeModel;
     using TUNet::State;
     using TUNet::UpdateMsg;
@@ -32,6 +34,13 @@ extern "C"
         std::uint64_t flux;
     };
 
+    struct NativeEstimate
+    {
+        double charge;
+        std::uint64_t projected_flux;
+        double projected_charge;
+    };
+
     struct DetailGroup
     {
         std::int64_t logout_date;
@@ -47,6 +56,7 @@ extern "C"
     using MainCallback = int (*)(NativeModel, void*);
     using UpdateCallback = void (*)(UpdateMsg, void*);
     using StringCallback = void (*)(const char16_t*, void*);
+    using LogCallback = void (*)(LogLevel, const char16_t*, void*);
     using OnlinesForeachCallback = bool (*)(const OnlineUser*, void*);
     using DetailsForeachCallback = bool (*)(const Detail*, void*);
     using DetailsGroupedForeachCallback = bool (*)(const DetailGroup*, void*);
@@ -65,12 +75,14 @@ extern "C"
     std::uint32_t tunet_parse_ip(const char16_t* str);
     void tunet_format_mac_address(const std::uint8_t* addr, StringCallback f, void* data);
 
+    bool tunet_log_init(LogLevel level, LogCallback f, void* data);
     std::int32_t tunet_model_start(std::size_t val, MainCallback main, void* data);
     void tunet_model_set_update_callback(NativeModel m, UpdateCallback update, void* data);
     void tunet_model_queue(NativeModel m, Action a);
     bool tunet_model_queue_cred_load(NativeModel m);
     void tunet_model_queue_cred(NativeModel m, const char16_t* u, const char16_t* p);
     void tunet_model_queue_state(NativeModel m, State s);
+    void tunet_model_queue_profile(NativeModel m, const char16_t* p);
     void tunet_model_queue_connect(NativeModel m, std::uint32_t addr);
     void tunet_model_queue_drop(NativeModel m, std::uint32_t addr);
     void tunet_model_status(NativeModel m, StringCallback f, void* data);
@@ -83,6 +95,10 @@ extern "C"
     std::uint64_t tunet_model_flux_flux(NativeModel m);
     std::int64_t tunet_model_flux_online_time(NativeModel m);
     double tunet_model_flux_balance(NativeModel m);
+    NativeEstimate tunet_model_estimate(NativeModel m);
+    void tunet_model_alerts(NativeModel m, StringCallback f, void* data);
+    void tunet_model_profile(NativeModel m, StringCallback f, void* data);
+    void tunet_model_profiles(StringCallback f, void* data);
     void tunet_model_onlines_foreach(NativeModel m, OnlinesForeachCallback f, void* data);
     void tunet_model_details_foreach(NativeModel m, DetailsForeachCallback f, void* data);
     void tunet_model_details_grouped_foreach(NativeModel m, DetailsGroupedForeachCallback f, void* data);
@@ -143,8 +159,33 @@ namespace TUNet
         return (d->main)(d->argc, d->argv, &model);
     }
 
+    static void fn_log_callback(LogLevel level, const char16_t* message, void*)
+    {
+        QString msg = QString::fromUtf16(message);
+        switch (level)
+        {
+        case LogLevel::Error:
+            qCritical().noquote() << msg;
+            break;
+        case LogLevel::Warn:
+            qWarning().noquote() << msg;
+            break;
+        case LogLevel::Info:
+            qInfo().noquote() << msg;
+            break;
+        default:
+            qDebug().noquote() << msg;
+            break;
+        }
+    }
+
     std::int32_t Model::start(std::size_t threads, StartCallback main, int argc, char** argv)
     {
+#ifdef NDEBUG
+        tunet_log_init(LogLevel::Warn, fn_log_callback, nullptr);
+#else
+        tunet_log_init(LogLevel::Debug, fn_log_callback, nullptr);
+#endif
         init_data data{ main, argc, argv };
         return tunet_model_start(threads, fn_init_callback, &data);
     }
@@ -180,6 +221,8 @@ namespace TUNet
 
     void Model::queue_state(State s) const { tunet_model_queue_state(m_handle, s); }
 
+    void Model::queue_profile(const QString& profile) const { tunet_model_queue_profile(m_handle, QSTRING_UTF16(profile)); }
+
     void Model::queue_connect(Ipv4Addr addr) const { tunet_model_queue_connect(m_handle, addr); }
 
     void Model::queue_drop(Ipv4Addr addr) const { tunet_model_queue_drop(m_handle, addr); }
@@ -215,6 +258,12 @@ namespace TUNet
         case UpdateMsg::DetailBusy:
             emit detail_busy_changed();
             break;
+        case UpdateMsg::Alert:
+            emit alerts_changed();
+            break;
+        case UpdateMsg::Profile:
+            emit profile_changed();
+            break;
         }
     }
 
@@ -254,12 +303,27 @@ namespace TUNet
         return Info{ std::move(username), f, std::chrono::seconds{ online }, balance };
     }
 
+    Estimate Model::estimate() const
+    {
+        auto e = tunet_model_estimate(m_handle);
+        return Estimate{ e.charge, e.projected_flux, e.projected_charge };
+    }
+
+    QString Model::alerts() const { return get_q_string(tunet_model_alerts, m_handle); }
+
+    QString Model::profile() const { return get_q_string(tunet_model_profile, m_handle); }
+
+    QStringList Model::profiles() { return get_q_string(tunet_model_profiles).split(u'\n', Qt::SkipEmptyParts); }
+
+    // Timestamps are shown in the campus timezone, UTC+8.
+    constexpr int CAMPUS_UTC_OFFSET = 8 * 3600;
+
     static bool fn_foreach_online(const OnlineUser* u, void* data)
     {
         auto& users = *reinterpret_cast<std::vector<Online>*>(data);
         users.emplace_back(Online{
             u->address,
-            QDateTime::fromSecsSinceEpoch(u->login_time, Qt::UTC),
+            QDateTime::fromSecsSinceEpoch(u->login_time, Qt::OffsetFromUTC, CAMPUS_UTC_OFFSET),
             u->flux,
             u->has_mac ? std::make_optional(MacAddress{ u->mac_address }) : std::nullopt,
             u->is_local });
@@ -277,8 +341,8 @@ namespace TUNet
     {
         auto& details = *reinterpret_cast<std::vector<Detail>*>(data);
         details.emplace_back(Detail{
-            QDateTime::fromSecsSinceEpoch(d->login_time, Qt::UTC),
-            QDateTime::fromSecsSinceEpoch(d->logout_time, Qt::UTC),
+            QDateTime::fromSecsSinceEpoch(d->login_time, Qt::OffsetFromUTC, CAMPUS_UTC_OFFSET),
+            QDateTime::fromSecsSinceEpoch(d->logout_time, Qt::OffsetFromUTC, CAMPUS_UTC_OFFSET),
             d->flux });
         return true;
     }
@@ -293,7 +357,7 @@ namespace TUNet
     static bool fn_foreach_detail_group(const DetailGroup* d, void* data)
     {
         auto& details = *reinterpret_cast<std::map<QDate, Flux>*>(data);
-        details.emplace(QDateTime::fromSecsSinceEpoch(d->logout_date, Qt::UTC).date(), d->flux);
+        details.emplace(QDateTime::fromSecsSinceEpoch(d->logout_date, Qt::OffsetFromUTC, CAMPUS_UTC_OFFSET).date(), d->flux);
         return true;
     }
 
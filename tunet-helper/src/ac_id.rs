@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MAX_FAILURES: u32 = 3;
+
+/// 超过这么久没有登录成功的 ac_id 不再使用。
+const TTL: i64 = 30 * 24 * 60 * 60;
+
+static DEFAULT_NETWORK: &str = "default";
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AcIdEntry {
+    pub id: i32,
+    #[serde(default)]
+    pub failures: u32,
+    /// 最近一次登录成功的 Unix 时间戳；旧的记录从读取时开始计算。
+    #[serde(default = "now")]
+    pub succeeded_at: i64,
+}
+
+impl AcIdEntry {
+    fn expired(&self, now: i64) -> bool {
+        now - self.succeeded_at > TTL
+    }
+}
+
+/// 按网络记录可用的 ac_id，最近成功的排在最前。
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct AcIdCache(BTreeMap<String, Vec<AcIdEntry>>);
+
+impl AcIdCache {
+    fn key(network: Option<&str>) -> &str {
+        network.unwrap_or(DEFAULT_NETWORK)
+    }
+
+    /// 当前网络下依次尝试的 ac_id，之后是未区分网络的记录。
+    pub fn candidates(&self, network: Option<&str>) -> Vec<i32> {
+        self.candidates_at(network, now())
+    }
+
+    fn candidates_at(&self, network: Option<&str>, now: i64) -> Vec<i32> {
+        let mut ids = self
+            .0
+            .get(Self::key(network))
+            .into_iter()
+            .chain(self.0.get(DEFAULT_NETWORK))
+            .flatten()
+            .filter(|e| !e.expired(now))
+            .map(|e| e.id)
+            .collect::<Vec<_>>();
+        let mut seen = Vec::with_capacity(ids.len());
+        ids.retain(|id| {
+            let fresh = !seen.contains(id);
+            seen.push(*id);
+            fresh
+        });
+        ids
+    }
+
+    pub fn succeeded(&mut self, network: Option<&str>, id: i32) {
+        self.succeeded_at(network, id, now())
+    }
+
+    fn succeeded_at(&mut self, network: Option<&str>, id: i32, now: i64) {
+        let entries = self.0.entry(Self::key(network).to_string()).or_default();
+        entries.retain(|e| e.id != id);
+        entries.insert(
+            0,
+            AcIdEntry {
+                id,
+                failures: 0,
+                succeeded_at: now,
+            },
+        );
+        self.prune(now);
+    }
+
+    /// 记录确认不适用于当前网络的 ac_id，多次失败后移除。
+    pub fn failed(&mut self, network: Option<&str>, id: i32) {
+        self.failed_at(network, id, now())
+    }
+
+    fn failed_at(&mut self, network: Option<&str>, id: i32, now: i64) {
+        let key = Self::key(network);
+        for (_, entries) in self
+            .0
+            .iter_mut()
+            .filter(|(k, _)| *k == key || *k == DEFAULT_NETWORK)
+        {
+            for e in entries.iter_mut().filter(|e| e.id == id) {
+                e.failures += 1;
+            }
+        }
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: i64) {
+        for entries in self.0.values_mut() {
+            entries.retain(|e| e.failures < MAX_FAILURES && !e.expired(now));
+        }
+        self.0.retain(|_, entries| !entries.is_empty());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<i32>> for AcIdCache {
+    fn from(ids: Vec<i32>) -> Self {
+        let mut cache = Self::default();
+        for id in ids.into_iter().rev() {
+            cache.succeeded(None, id);
+        }
+        cache
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn candidates_order() {
+        let mut cache = AcIdCache::default();
+        cache.succeeded_at(None, 1, NOW);
+        cache.succeeded_at(Some("wifi"), 30, NOW);
+        cache.succeeded_at(Some("wifi"), 159, NOW + 1);
+        cache.succeeded_at(None, 159, NOW + 2);
+        assert_eq!(cache.candidates_at(Some("wifi"), NOW + 3), [159, 30, 1]);
+        assert_eq!(cache.candidates_at(Some("lan"), NOW + 3), [159, 1]);
+        assert_eq!(cache.candidates_at(None, NOW + 3), [159, 1]);
+    }
+
+    #[test]
+    fn failures_prune() {
+        let mut cache = AcIdCache::default();
+        cache.succeeded_at(Some("wifi"), 30, NOW);
+        cache.succeeded_at(Some("wifi"), 159, NOW);
+        for _ in 1..MAX_FAILURES {
+            cache.failed_at(Some("wifi"), 159, NOW);
+        }
+        assert_eq!(cache.candidates_at(Some("wifi"), NOW), [159, 30]);
+        cache.failed_at(Some("wifi"), 159, NOW);
+        assert_eq!(cache.candidates_at(Some("wifi"), NOW), [30]);
+        for _ in 0..MAX_FAILURES {
+            cache.failed_at(Some("wifi"), 30, NOW);
+        }
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let mut cache = AcIdCache::default();
+        cache.succeeded_at(None, 1, NOW);
+        for _ in 1..MAX_FAILURES {
+            cache.failed_at(None, 1, NOW);
+        }
+        cache.succeeded_at(None, 1, NOW);
+        cache.failed_at(None, 1, NOW);
+        assert_eq!(cache.candidates_at(None, NOW), [1]);
+    }
+
+    #[test]
+    fn expiry() {
+        let mut cache = AcIdCache::default();
+        cache.succeeded_at(Some("wifi"), 30, NOW);
+        cache.succeeded_at(Some("wifi"), 159, NOW + TTL);
+        assert_eq!(cache.candidates_at(Some("wifi"), NOW + TTL), [159, 30]);
+        assert_eq!(cache.candidates_at(Some("wifi"), NOW + TTL + 1), [159]);
+        cache.succeeded_at(None, 1, NOW + TTL + 1);
+        assert_eq!(cache.0["wifi"].len(), 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut cache = AcIdCache::default();
+        cache.succeeded_at(Some("wifi"), 30, NOW);
+        cache.succeeded_at(None, 1, NOW);
+        cache.failed_at(None, 1, NOW);
+        let json = serde_json::to_string(&cache).unwrap();
+        assert_eq!(serde_json::from_str::<AcIdCache>(&json).unwrap(), cache);
+    }
+
+    #[test]
+    fn legacy_entries() {
+        let cache: AcIdCache = serde_json::from_str(r#"{"default":[{"id":1}]}"#).unwrap();
+        assert_eq!(cache.candidates(None), [1]);
+        assert_eq!(cache.0["default"][0].failures, 0);
+    }
+}
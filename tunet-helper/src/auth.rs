@@ -1,30 +1,79 @@
 use crate::*;
-use authtea::AuthTea;
-use data_encoding::{Encoding, HEXLOWER};
-use data_encoding_macro::new_encoding;
-use hmac::{Hmac, Mac};
 use lazy_static::lazy_static;
-use md5::Md5;
 use regex::Regex;
-use serde_json::{json, Value as JsonValue};
-use sha1::{Digest, Sha1};
+use reqwest::header::LOCATION;
+#[cfg(feature = "html")]
+use select::document::Document;
+#[cfg(feature = "html")]
+use select::predicate::Name;
+use serde_json::Value as JsonValue;
 use std::marker::PhantomData;
+use std::net::IpAddr;
 use url::Url;
 
 #[derive(Clone)]
 pub struct AuthConnect<U: AuthConnectUri + Send + Sync> {
     cred: Arc<NetCredential>,
     client: HttpClient,
+    ip: Option<IpAddr>,
+    portal: Arc<PortalProfile>,
+    retry: RetryPolicy,
+    fixtures: Option<Arc<Fixtures>>,
     _p: PhantomData<U>,
 }
 
-const AUTH_BASE64: Encoding = new_encoding! {
-    symbols: "LVoJPiCN2R8G90yg+hmFHuacZ1OWMnrsSTXkYpUq/3dlbfKwv6xztjI7DeBE45QA",
-    padding: '=',
-};
+const MAX_REDIRECTS: usize = 10;
+
+/// 当前网络的标识，用于按网络缓存 ac_id；未启用 `netstatus` 特性时不区分网络。
+/// 查询网络状态会阻塞，因此放到阻塞线程中进行。
+#[cfg(feature = "netstatus")]
+async fn current_network() -> Option<String> {
+    tokio::task::spawn_blocking(|| netstatus::NetStatus::current().network())
+        .await
+        .ok()
+        .flatten()
+}
+
+#[cfg(not(feature = "netstatus"))]
+async fn current_network() -> Option<String> {
+    None
+}
 
 lazy_static! {
     static ref AC_ID_REGEX: Regex = Regex::new(r"/index_([0-9]+)\.html").unwrap();
+    static ref AC_ID_QUERY_REGEX: Regex = Regex::new(r"(?-u:\b)ac_id=([0-9]+)").unwrap();
+}
+
+fn find_ac_id(s: &str) -> Option<i32> {
+    AC_ID_REGEX
+        .captures(s)
+        .or_else(|| AC_ID_QUERY_REGEX.captures(s))
+        .and_then(|cap| cap[1].parse().ok())
+}
+
+#[cfg(feature = "html")]
+fn meta_refresh(html: &str) -> Option<String> {
+    Document::from(html)
+        .find(Name("meta"))
+        .filter(|n| {
+            n.attr("http-equiv")
+                .map(|e| e.eq_ignore_ascii_case("refresh"))
+                .unwrap_or(false)
+        })
+        .filter_map(|n| n.attr("content"))
+        .find_map(|content| {
+            let pos = content.to_ascii_lowercase().find("url=")?;
+            Some(
+                content[pos + 4..]
+                    .trim_matches(|c: char| c.is_whitespace() || c == '\'' || c == '"')
+                    .to_string(),
+            )
+        })
+}
+
+#[cfg(not(feature = "html"))]
+fn meta_refresh(_html: &str) -> Option<String> {
+    None
 }
 
 impl<U: AuthConnectUri + Send + Sync> AuthConnect<U> {
@@ -32,22 +81,57 @@ impl<U: AuthConnectUri + Send + Sync> AuthConnect<U> {
         Self {
             cred,
             client,
+            ip: None,
+            portal: Arc::new(U::portal()),
+            retry: RetryPolicy::default(),
+            fixtures: None,
             _p: PhantomData::default(),
         }
     }
 
+    pub fn with_portal(mut self, portal: Arc<PortalProfile>) -> Self {
+        self.portal = portal;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_fixtures(mut self, fixtures: Option<Arc<Fixtures>>) -> Self {
+        if let Some(f) = &fixtures {
+            f.add_secret(&self.cred.username);
+        }
+        self.fixtures = fixtures;
+        self
+    }
+
+    pub fn with_ip(mut self, ip: Option<IpAddr>) -> Self {
+        self.ip = ip;
+        self
+    }
+
+    fn ip(&self) -> String {
+        self.ip.map(|ip| ip.to_string()).unwrap_or_default()
+    }
+
     async fn challenge(&self) -> Result<String> {
         let uri = Url::parse_with_params(
-            U::challenge_uri(),
+            &self.portal.challenge_uri,
             &[
-                ("username", self.cred.username.as_ref()),
-                ("double_stack", "1"),
-                ("ip", ""),
+                ("username", self.cred.username.as_str()),
+                ("double_stack", &self.portal.double_stack),
+                ("ip", &self.ip()),
                 ("callback", "callback"),
             ],
+        )?;
+        let res = trace::send(
+            "获取 challenge",
+            self.client.get(uri),
+            self.fixtures.as_deref(),
         )
-        .unwrap();
-        let res = self.client.get(uri).send().await?;
+        .await?;
         let t = res.text().await?;
         let mut json: JsonValue = serde_json::from_str(&t[9..t.len() - 1])?;
         Ok(json
@@ -57,63 +141,109 @@ impl<U: AuthConnectUri + Send + Sync> AuthConnect<U> {
     }
 
     async fn get_ac_id(&self) -> Result<i32> {
-        let res = self.client.get(U::redirect_uri()).send().await?;
-        let t = res.text().await?;
-        match AC_ID_REGEX.captures(&t) {
-            Some(cap) => Ok(cap[1].parse::<i32>()?),
-            _ => Err(NetHelperError::NoAcId.into()),
+        let mut uri = Url::parse(&self.portal.redirect_uri)?;
+        let mut seen = Vec::new();
+        for _ in 0..MAX_REDIRECTS {
+            seen.push(uri.to_string());
+            if let Some(ac_id) = find_ac_id(uri.as_str()) {
+                return Ok(ac_id);
+            }
+            let res = trace::send(
+                "获取 ac_id",
+                self.client.get(uri.clone()),
+                self.fixtures.as_deref(),
+            )
+            .await?;
+            let location = res
+                .headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .map(|l| l.to_string());
+            let next = match location {
+                Some(location) => Some(location),
+                None => {
+                    let t = res.text().await?;
+                    if let Some(ac_id) = find_ac_id(&t) {
+                        return Ok(ac_id);
+                    }
+                    meta_refresh(&t)
+                }
+            };
+            match next {
+                Some(next) => uri = uri.join(&next)?,
+                None => break,
+            }
         }
+        Err(NetHelperError::NoAcId(seen.join(" -> ")).into())
     }
 
     async fn try_login(&self, ac_id: i32) -> Result<String> {
+        let portal = &self.portal;
+        let encoding = portal.encoding()?;
         let token = self.challenge().await?;
-        let password_md5 = {
-            let mut hmacmd5 = Hmac::<Md5>::new_from_slice(&[]).unwrap();
-            hmacmd5.update(token.as_bytes());
-            hmacmd5.finalize().into_bytes()
-        };
-        let password_md5 = HEXLOWER.encode(&password_md5);
-        let encode_json = json!({
-            "username": self.cred.username,
-            "password": self.cred.password,
-            "ip": "",
-            "acid": ac_id,
-            "enc_ver": "srun_bx1"
-        });
-        let info = {
-            let tea = AuthTea::new(token.as_bytes());
-            tea.encode(encode_json.to_string().as_bytes())
-        };
-        let info = format!("{{SRBX1}}{}", AUTH_BASE64.encode(&info));
-        let chksum = {
-            let mut sha1 = Sha1::new();
-            sha1.update(format!(
-                "{0}{1}{0}{2}{0}{4}{0}{0}200{0}1{0}{3}",
-                token, self.cred.username, password_md5, info, ac_id
-            ));
-            sha1.finalize()
+        let ip = self.ip();
+        let login = srun::Login {
+            username: &self.cred.username,
+            password: self.cred.password.expose(),
+            ip: &ip,
+            ac_id,
+            enc_ver: &portal.enc_ver,
+            n: &portal.n,
+            ty: &portal.ty,
         };
-        let params = [
+        let p = login.params(&token, &encoding, &portal.chksum);
+        let ac_id = ac_id.to_string();
+        let mut params = vec![
             ("action", "login"),
-            ("ac_id", &ac_id.to_string()),
-            ("double_stack", "1"),
-            ("n", "200"),
-            ("type", "1"),
+            ("ac_id", ac_id.as_str()),
+            ("ip", &ip),
+            ("double_stack", &portal.double_stack),
+            ("n", &portal.n),
+            ("type", &portal.ty),
             ("username", &self.cred.username),
-            ("password", &format!("{{MD5}}{}", password_md5)),
-            ("info", &info),
-            ("chksum", &HEXLOWER.encode(&chksum)),
+            ("password", &p.password),
+            ("info", &p.info),
+            ("chksum", &p.chksum),
             ("callback", "callback"),
         ];
-        let res = self.client.post(U::log_uri()).form(&params).send().await?;
+        params.extend(portal.extra.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        let res = trace::send(
+            "登录",
+            self.client.post(&portal.log_uri).form(&params),
+            self.fixtures.as_deref(),
+        )
+        .await?;
         let t = res.text().await?;
         Self::parse_response(&t)
     }
 
+    #[tracing::instrument(name = "auth_drop", skip(self), fields(username = %self.cred.username))]
+    pub async fn drop(&self, ip: IpAddr) -> Result<String> {
+        let time = Local::now().timestamp().to_string();
+        let ip = ip.to_string();
+        let sign = srun::drop_sign(&time, &self.cred.username, &ip);
+        let uri = Url::parse_with_params(
+            &self.portal.drop_uri,
+            &[
+                ("ip", ip.as_str()),
+                ("username", &self.cred.username),
+                ("time", &time),
+                ("unbind", "1"),
+                ("sign", &sign),
+                ("callback", "callback"),
+            ],
+        )?;
+        let t = self
+            .retry
+            .text("下线", self.client.get(uri), self.fixtures.as_deref())
+            .await?;
+        Self::parse_response(&t)
+    }
+
     fn parse_response(t: &str) -> Result<String> {
         let mut json: JsonValue = serde_json::from_str(&t[9..t.len() - 1])?;
         if let Some(error) = json["error"].as_str() {
-            if error == "ok" {
+            if error == "ok" || error == "logout_ok" {
                 Ok(json
                     .remove("suc_msg")
                     .and_then(|v| v.into_str())
@@ -134,34 +264,99 @@ impl<U: AuthConnectUri + Send + Sync> AuthConnect<U> {
 
 #[async_trait]
 impl<U: AuthConnectUri + Send + Sync> TUNetHelper for AuthConnect<U> {
+    #[tracing::instrument(
+        name = "auth_login",
+        skip_all,
+        fields(uri = %self.portal.log_uri, username = %self.cred.username)
+    )]
     async fn login(&self) -> Result<String> {
-        for ac_id in self.cred.ac_ids.read().await.iter() {
-            let res = self.try_login(*ac_id).await;
-            if res.is_ok() {
-                return res;
+        let network = current_network().await;
+        let network = network.as_deref();
+        let ac_ids = self.cred.ac_ids.read().await.candidates(network);
+        let mut rejected = Vec::new();
+        for ac_id in ac_ids {
+            tracing::debug!(ac_id, "尝试缓存的 ac_id");
+            match self.retry.run("登录", || self.try_login(ac_id)).await {
+                Ok(res) => {
+                    self.cred.ac_ids.write().await.succeeded(network, ac_id);
+                    return Ok(res);
+                }
+                Err(e) if e.is::<NetHelperError>() => {
+                    tracing::debug!(ac_id, error = %e, "ac_id 登录失败");
+                    rejected.push((ac_id, e));
+                }
+                Err(e) => tracing::debug!(ac_id, error = %e, "登录请求失败"),
             }
         }
-        let ac_id = self.get_ac_id().await?;
-        self.cred.ac_ids.write().await.push(ac_id);
-        Ok(self.try_login(ac_id).await?)
+        let ac_id = match self.get_ac_id().await {
+            Ok(ac_id) => ac_id,
+            // 已在线等情况下没有重定向，无法判断 ac_id 是否有误，直接返回门户的错误。
+            Err(e) => return Err(rejected.pop().map(|(_, e)| e).unwrap_or(e)),
+        };
+        tracing::debug!(ac_id, "从重定向中获取 ac_id");
+        // 只有与重定向给出的不同的 ac_id 才算错误，密码错误等不影响缓存。
+        {
+            let mut cache = self.cred.ac_ids.write().await;
+            for (id, _) in rejected.iter().filter(|(id, _)| *id != ac_id) {
+                cache.failed(network, *id);
+            }
+        }
+        if let Some((_, e)) = rejected.into_iter().find(|(id, _)| *id == ac_id) {
+            return Err(e);
+        }
+        let res = self.retry.run("登录", || self.try_login(ac_id)).await?;
+        self.cred.ac_ids.write().await.succeeded(network, ac_id);
+        Ok(res)
     }
 
+    #[tracing::instrument(
+        name = "auth_logout",
+        skip_all,
+        fields(uri = %self.portal.log_uri, username = %self.cred.username)
+    )]
     async fn logout(&self) -> Result<String> {
+        let network = current_network().await;
+        let ac_id = self
+            .cred
+            .ac_ids
+            .read()
+            .await
+            .candidates(network.as_deref())
+            .first()
+            .copied()
+            .unwrap_or(1);
+        let ac_id = ac_id.to_string();
+        let ip = self.ip();
         let params = [
             ("action", "logout"),
-            ("ac_id", "1"),
-            ("double_stack", "1"),
+            ("ac_id", &ac_id),
+            ("ip", &ip),
+            ("double_stack", &self.portal.double_stack),
             ("username", &self.cred.username),
             ("callback", "callback"),
         ];
-        let res = self.client.post(U::log_uri()).form(&params).send().await?;
-        let t = res.text().await?;
+        let t = self
+            .retry
+            .text(
+                "注销",
+                self.client.post(&self.portal.log_uri).form(&params),
+                self.fixtures.as_deref(),
+            )
+            .await?;
         Self::parse_response(&t)
     }
 
+    #[tracing::instrument(name = "auth_flux", skip_all, fields(uri = %self.portal.flux_uri))]
     async fn flux(&self) -> Result<NetFlux> {
-        let res = self.client.get(U::flux_uri()).send().await?;
-        Ok(res.text().await?.parse()?)
+        let t = self
+            .retry
+            .text(
+                "获取在线状态",
+                self.client.get(&self.portal.flux_uri),
+                self.fixtures.as_deref(),
+            )
+            .await?;
+        Ok(t.parse()?)
     }
 
     fn cred(&self) -> Arc<NetCredential> {
@@ -170,10 +365,7 @@ impl<U: AuthConnectUri + Send + Sync> TUNetHelper for AuthConnect<U> {
 }
 
 pub trait AuthConnectUri {
-    fn log_uri() -> &'static str;
-    fn challenge_uri() -> &'static str;
-    fn flux_uri() -> &'static str;
-    fn redirect_uri() -> &'static str;
+    fn portal() -> PortalProfile;
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -181,23 +373,8 @@ pub struct Auth4Uri;
 
 impl AuthConnectUri for Auth4Uri {
     #[inline]
-    fn log_uri() -> &'static str {
-        "https://auth4.tsinghua.edu.cn/cgi-bin/srun_portal"
-    }
-
-    #[inline]
-    fn challenge_uri() -> &'static str {
-        "https://auth4.tsinghua.edu.cn/cgi-bin/get_challenge"
-    }
-
-    #[inline]
-    fn flux_uri() -> &'static str {
-        "https://auth4.tsinghua.edu.cn/rad_user_info.php"
-    }
-
-    #[inline]
-    fn redirect_uri() -> &'static str {
-        "http://3.3.3.3/"
+    fn portal() -> PortalProfile {
+        PortalProfile::auth4()
     }
 }
 
@@ -206,24 +383,15 @@ pub struct Auth6Uri;
 
 impl AuthConnectUri for Auth6Uri {
     #[inline]
-    fn log_uri() -> &'static str {
-        "https://auth6.tsinghua.edu.cn/cgi-bin/srun_portal"
-    }
-
-    #[inline]
-    fn challenge_uri() -> &'static str {
-        "https://auth6.tsinghua.edu.cn/cgi-bin/get_challenge"
-    }
-
-    #[inline]
-    fn flux_uri() -> &'static str {
-        "https://auth6.tsinghua.edu.cn/rad_user_info.php"
+    fn portal() -> PortalProfile {
+        PortalProfile::auth6()
     }
+}
 
-    #[inline]
-    fn redirect_uri() -> &'static str {
-        "http://[333::3]/"
-    }
+/// 还原登录请求中的 `info` 字段。
+pub fn decode_info(info: &str, token: &str, portal: &PortalProfile) -> Result<String> {
+    let data = srun::decode_info(info, token, &portal.encoding()?).map_err(srun_error)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
 }
 
 trait ExactString {
@@ -0,0 +1,324 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TariffTier {
+    pub from: Flux,
+    pub price: f64,
+}
+
+impl TariffTier {
+    pub fn new(from: Flux, price: f64) -> Self {
+        Self { from, price }
+    }
+}
+
+impl std::str::FromStr for TariffTier {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            Some((from, price)) => Ok(Self::new(
+                from.trim().parse()?,
+                price
+                    .trim()
+                    .parse()
+                    .map_err(|_| NetHelperError::InvalidTariff(s.to_string()))?,
+            )),
+            None => Err(NetHelperError::InvalidTariff(s.to_string()).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tariff {
+    tiers: Vec<TariffTier>,
+    pub cap: Option<Balance>,
+}
+
+const DEFAULT_FREE_GB: f64 = 20.0;
+const DEFAULT_PRICE: f64 = 1.0;
+
+impl Default for Tariff {
+    fn default() -> Self {
+        Self::new(
+            vec![TariffTier::new(
+                Flux::from_gb(DEFAULT_FREE_GB),
+                DEFAULT_PRICE,
+            )],
+            None,
+        )
+    }
+}
+
+impl Tariff {
+    pub fn new(mut tiers: Vec<TariffTier>, cap: Option<Balance>) -> Self {
+        tiers.sort_unstable_by_key(|t| t.from);
+        Self { tiers, cap }
+    }
+
+    /// 用命令行中指定的档位与上限覆盖。
+    pub fn with_overrides(mut self, tiers: &[TariffTier], cap: Option<f64>) -> Self {
+        if !tiers.is_empty() {
+            self = Self::new(tiers.to_vec(), self.cap);
+        }
+        if let Some(cap) = cap {
+            self.cap = Some(Balance(cap));
+        }
+        self
+    }
+
+    pub fn tiers(&self) -> &[TariffTier] {
+        &self.tiers
+    }
+
+    pub fn free_quota(&self) -> Flux {
+        self.tiers.first().map(|t| t.from).unwrap_or(Flux(u64::MAX))
+    }
+
+    pub fn charge(&self, flux: Flux) -> Balance {
+        let mut total = 0.0;
+        for (i, tier) in self.tiers.iter().enumerate() {
+            if flux <= tier.from {
+                break;
+            }
+            let upper = self
+                .tiers
+                .get(i + 1)
+                .map(|t| t.from.min(flux))
+                .unwrap_or(flux);
+            total += Flux(upper.0 - tier.from.0).to_gb() * tier.price;
+        }
+        if let Some(cap) = self.cap {
+            total = total.min(cap.0);
+        }
+        Balance(total)
+    }
+
+    pub fn estimate(
+        &self,
+        flux: Flux,
+        details: &[NetDetail],
+        method: ProjectionMethod,
+        now: DateTime<FixedOffset>,
+    ) -> Estimate {
+        let projected_flux = method.project(flux, details, now);
+        Estimate {
+            flux,
+            charge: self.charge(flux),
+            projected_flux,
+            projected_charge: self.charge(projected_flux),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionMethod {
+    Linear,
+    MovingAverage(u32),
+}
+
+const DEFAULT_AVERAGE_DAYS: u32 = 7;
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+impl ProjectionMethod {
+    /// 按最近一周明细的移动平均预测。
+    pub const AVERAGE: Self = Self::MovingAverage(DEFAULT_AVERAGE_DAYS);
+}
+
+/// 线性预测不需要流量明细。
+impl Default for ProjectionMethod {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+impl std::str::FromStr for ProjectionMethod {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("linear") {
+            Ok(Self::Linear)
+        } else if s.eq_ignore_ascii_case("average") {
+            Ok(Self::AVERAGE)
+        } else {
+            Err(NetHelperError::InvalidProjection.into())
+        }
+    }
+}
+
+fn days_between(start: NaiveDateTime, end: NaiveDateTime) -> f64 {
+    (end - start).num_seconds().max(0) as f64 / SECONDS_PER_DAY
+}
+
+fn month_bounds(now: NaiveDateTime) -> (NaiveDateTime, NaiveDateTime) {
+    let date = now.date();
+    let start = NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap();
+    let end = if date.month() == 12 {
+        NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1)
+    }
+    .unwrap();
+    (
+        start.and_hms_opt(0, 0, 0).unwrap(),
+        end.and_hms_opt(0, 0, 0).unwrap(),
+    )
+}
+
+impl ProjectionMethod {
+    pub fn project(&self, flux: Flux, details: &[NetDetail], now: DateTime<FixedOffset>) -> Flux {
+        // Months and days are counted in the campus timezone.
+        let tz = campus_tz();
+        let now = now.with_timezone(&tz).naive_local();
+        let (start, end) = month_bounds(now);
+        let remaining = days_between(now, end);
+        match self {
+            Self::MovingAverage(days) if !details.is_empty() => {
+                let window_start = (now.date() - NaiveDuration::days(*days as i64 - 1))
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+                    .max(start);
+                let window = days_between(window_start, now);
+                if window <= 0.0 {
+                    return flux;
+                }
+                let recent = details
+                    .iter()
+                    .filter(|d| d.logout_time.with_timezone(&tz).naive_local() >= window_start)
+                    .map(|d| d.flux.0)
+                    .sum::<u64>();
+                Flux(flux.0 + (recent as f64 / window * remaining) as u64)
+            }
+            _ => {
+                let elapsed = days_between(start, now);
+                if elapsed <= 0.0 {
+                    return flux;
+                }
+                Flux((flux.0 as f64 / elapsed * (elapsed + remaining)) as u64)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Estimate {
+    pub flux: Flux,
+    pub charge: Balance,
+    pub projected_flux: Flux,
+    pub projected_charge: Balance,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(d: u32, h: u32) -> DateTime<FixedOffset> {
+        campus_tz()
+            .from_local_datetime(
+                &NaiveDate::from_ymd_opt(2023, 1, d)
+                    .unwrap()
+                    .and_hms_opt(h, 0, 0)
+                    .unwrap(),
+            )
+            .unwrap()
+    }
+
+    fn detail(d: u32, gb: f64) -> NetDetail {
+        NetDetail::from_detail(time(d, 8), time(d, 9), Flux::from_gb(gb))
+    }
+
+    fn tariff(cap: Option<f64>) -> Tariff {
+        Tariff::new(
+            vec!["50G:2".parse().unwrap(), "20G:1".parse().unwrap()],
+            cap.map(Balance),
+        )
+    }
+
+    #[test]
+    fn parse_flux() {
+        assert_eq!("15G".parse::<Flux>().unwrap(), Flux::from_gb(15.0));
+        assert_eq!("1.5 GB".parse::<Flux>().unwrap(), Flux(1_500_000_000));
+        assert_eq!("200m".parse::<Flux>().unwrap(), Flux(200_000_000));
+        assert_eq!("12K".parse::<Flux>().unwrap(), Flux(12_000));
+        assert_eq!("34".parse::<Flux>().unwrap(), Flux(34));
+        for s in ["", " ", "G", "20X", "20TB", "-1G", "abc"] {
+            assert!(s.parse::<Flux>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn parse_tier() {
+        let tier: TariffTier = "20GB:1.5".parse().unwrap();
+        assert_eq!(tier, TariffTier::new(Flux::from_gb(20.0), 1.5));
+        for s in ["", ":", "20G", "20G:", ":1", "20X:1", "20G:x"] {
+            assert!(s.parse::<TariffTier>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn charge() {
+        let tariff = tariff(None);
+        assert_eq!(tariff.free_quota(), Flux::from_gb(20.0));
+        assert_eq!(tariff.charge(Flux::from_gb(10.0)), Balance(0.0));
+        assert_eq!(tariff.charge(Flux::from_gb(20.0)), Balance(0.0));
+        assert_eq!(tariff.charge(Flux::from_gb(30.0)), Balance(10.0));
+        assert_eq!(tariff.charge(Flux::from_gb(60.0)), Balance(50.0));
+    }
+
+    #[test]
+    fn cap() {
+        let tariff = tariff(Some(40.0));
+        assert_eq!(tariff.charge(Flux::from_gb(30.0)), Balance(10.0));
+        assert_eq!(tariff.charge(Flux::from_gb(60.0)), Balance(40.0));
+    }
+
+    #[test]
+    fn overrides() {
+        let tariff = Tariff::default().with_overrides(&[], Some(10.0));
+        assert_eq!(tariff.free_quota(), Flux::from_gb(DEFAULT_FREE_GB));
+        assert_eq!(tariff.cap, Some(Balance(10.0)));
+        let tariff = tariff.with_overrides(&[TariffTier::new(Flux::from_gb(5.0), 3.0)], None);
+        assert_eq!(tariff.free_quota(), Flux::from_gb(5.0));
+        assert_eq!(tariff.cap, Some(Balance(10.0)));
+    }
+
+    #[test]
+    fn project_linear() {
+        let flux = Flux::from_gb(10.0);
+        let projected = ProjectionMethod::Linear.project(flux, &[], time(11, 0));
+        assert_eq!(projected, Flux::from_gb(31.0));
+        assert_eq!(
+            ProjectionMethod::Linear.project(flux, &[], time(1, 0)),
+            flux
+        );
+    }
+
+    #[test]
+    fn project_average() {
+        let flux = Flux::from_gb(8.0);
+        let details = [detail(2, 5.0), detail(6, 3.0)];
+        let projected = ProjectionMethod::AVERAGE.project(flux, &details, time(11, 0));
+        assert_eq!(projected, Flux::from_gb(18.5));
+        // 没有明细时退回线性预测。
+        let projected = ProjectionMethod::AVERAGE.project(Flux::from_gb(10.0), &[], time(11, 0));
+        assert_eq!(projected, Flux::from_gb(31.0));
+    }
+
+    #[test]
+    fn project_average_month_start() {
+        let details = [detail(1, 2.0)];
+        let projected = ProjectionMethod::AVERAGE.project(Flux::from_gb(2.0), &details, time(3, 0));
+        assert_eq!(projected, Flux::from_gb(2.0 + 1.0 * 29.0));
+    }
+
+    #[test]
+    fn estimate() {
+        let e = tariff(None).estimate(
+            Flux::from_gb(10.0),
+            &[],
+            ProjectionMethod::Linear,
+            time(11, 0),
+        );
+        assert_eq!(e.charge, Balance(0.0));
+        assert_eq!(e.projected_flux, Flux::from_gb(31.0));
+        assert_eq!(e.projected_charge, Balance(11.0));
+    }
+}
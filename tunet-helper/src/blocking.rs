@@ -0,0 +1,63 @@
+//! 同步接口，内部使用单线程的 tokio 运行时。
+
+use crate::*;
+use tokio::runtime::{Builder as RuntimeBuilder, Runtime};
+
+#[cfg(feature = "usereg")]
+mod usereg;
+
+#[cfg(feature = "usereg")]
+pub use usereg::UseregHelper;
+
+fn runtime() -> Result<Runtime> {
+    Ok(RuntimeBuilder::new_current_thread().enable_all().build()?)
+}
+
+pub struct TUNetConnect {
+    inner: crate::TUNetConnect,
+    rt: Runtime,
+}
+
+impl TUNetConnect {
+    pub fn new(s: NetState, cred: Arc<NetCredential>, client: HttpClient) -> Result<Self> {
+        Self::from_async(crate::TUNetConnect::new(s, cred, client)?)
+    }
+
+    pub fn from_async(inner: crate::TUNetConnect) -> Result<Self> {
+        Ok(Self {
+            inner,
+            rt: runtime()?,
+        })
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.inner = self.inner.with_retry(retry);
+        self
+    }
+
+    pub fn with_fixtures(mut self, fixtures: Option<Arc<Fixtures>>) -> Self {
+        self.inner = self.inner.with_fixtures(fixtures);
+        self
+    }
+
+    pub fn login(&self) -> Result<String> {
+        self.rt.block_on(self.inner.login())
+    }
+
+    pub fn logout(&self) -> Result<String> {
+        self.rt.block_on(self.inner.logout())
+    }
+
+    pub fn flux(&self) -> Result<NetFlux> {
+        self.rt.block_on(self.inner.flux())
+    }
+
+    #[cfg(feature = "auth")]
+    pub fn drop(&self, ip: IpAddr) -> Result<String> {
+        self.rt.block_on(self.inner.drop(ip))
+    }
+
+    pub fn cred(&self) -> Arc<NetCredential> {
+        self.inner.cred()
+    }
+}
@@ -0,0 +1,87 @@
+use super::runtime;
+use crate::usereg::{NetDetail, NetDetailOrder, NetUser};
+use crate::*;
+use futures_core::Stream;
+use std::net::Ipv4Addr;
+use std::pin::Pin;
+use tokio::runtime::Runtime;
+
+pub struct UseregHelper {
+    inner: crate::usereg::UseregHelper,
+    rt: Runtime,
+}
+
+impl UseregHelper {
+    pub fn new(cred: Arc<NetCredential>, client: HttpClient) -> Result<Self> {
+        Ok(Self {
+            inner: crate::usereg::UseregHelper::new(cred, client),
+            rt: runtime()?,
+        })
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.inner = self.inner.with_retry(retry);
+        self
+    }
+
+    pub fn with_fixtures(mut self, fixtures: Option<Arc<Fixtures>>) -> Self {
+        self.inner = self.inner.with_fixtures(fixtures);
+        self
+    }
+
+    pub fn login(&self) -> Result<String> {
+        self.rt.block_on(self.inner.login())
+    }
+
+    pub fn logout(&self) -> Result<String> {
+        self.rt.block_on(self.inner.logout())
+    }
+
+    pub fn connect(&self, addr: Ipv4Addr) -> Result<String> {
+        self.rt.block_on(self.inner.connect(addr))
+    }
+
+    pub fn drop(&self, addr: Ipv4Addr) -> Result<String> {
+        self.rt.block_on(self.inner.drop(addr))
+    }
+
+    pub fn users(&self) -> impl Iterator<Item = Result<NetUser>> + '_ {
+        BlockingIter::new(&self.rt, self.inner.users())
+    }
+
+    pub fn details(
+        &self,
+        o: NetDetailOrder,
+        des: bool,
+    ) -> impl Iterator<Item = Result<NetDetail>> + '_ {
+        BlockingIter::new(&self.rt, self.inner.details(o, des))
+    }
+
+    pub fn cred(&self) -> Arc<NetCredential> {
+        self.inner.cred()
+    }
+}
+
+struct BlockingIter<'a, S> {
+    rt: &'a Runtime,
+    stream: Pin<Box<S>>,
+}
+
+impl<'a, S: Stream> BlockingIter<'a, S> {
+    fn new(rt: &'a Runtime, stream: S) -> Self {
+        Self {
+            rt,
+            stream: Box::pin(stream),
+        }
+    }
+}
+
+impl<'a, S: Stream> Iterator for BlockingIter<'a, S> {
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let stream = &mut self.stream;
+        self.rt
+            .block_on(std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)))
+    }
+}
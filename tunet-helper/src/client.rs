@@ -0,0 +1,91 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration as StdDuration;
+
+/// HTTP 客户端选项，未设置的项使用默认值。
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpClientOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_address: Option<IpAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+impl HttpClientOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connect_timeout(mut self, secs: u64) -> Self {
+        self.connect_timeout = Some(secs);
+        self
+    }
+
+    pub fn timeout(mut self, secs: u64) -> Self {
+        self.timeout = Some(secs);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn local_address(mut self, addr: IpAddr) -> Self {
+        self.local_address = Some(addr);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// 未设置的项使用 `other` 中的值。
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            connect_timeout: self.connect_timeout.or(other.connect_timeout),
+            timeout: self.timeout.or(other.timeout),
+            proxy: self.proxy.or(other.proxy),
+            local_address: self.local_address.or(other.local_address),
+            user_agent: self.user_agent.or(other.user_agent),
+        }
+    }
+
+    pub fn build(&self) -> Result<HttpClient> {
+        let mut builder = reqwest::ClientBuilder::new()
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy();
+        #[cfg(feature = "usereg")]
+        {
+            builder = builder.cookie_store(true);
+            if let Some(proxy) = &self.proxy {
+                builder = builder.proxy(usereg::proxy(proxy)?);
+            }
+        }
+        #[cfg(not(feature = "usereg"))]
+        if self.proxy.is_some() {
+            return Err(NetHelperError::UnsupportedProxy.into());
+        }
+        if let Some(secs) = self.connect_timeout {
+            builder = builder.connect_timeout(StdDuration::from_secs(secs));
+        }
+        if let Some(secs) = self.timeout {
+            builder = builder.timeout(StdDuration::from_secs(secs));
+        }
+        if let Some(addr) = self.local_address {
+            builder = builder.local_address(addr);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        Ok(builder.build()?)
+    }
+}
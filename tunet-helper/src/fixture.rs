@@ -0,0 +1,399 @@
+use crate::trace::{redact_json, redact_pairs, redact_url_with, REDACTED};
+use crate::*;
+use reqwest::header::LOCATION;
+use reqwest::{Request, Response};
+use serde::{Deserialize, Serialize};
+use std::fs::{DirBuilder, File};
+use std::io::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use url::{form_urlencoded, Url};
+
+/// 夹具中还会隐去这些参数的值。
+const SCRUB_KEYS: &[&str] = &["username", "user_login_name"];
+
+/// 回放时不比较这些参数的值：它们随时间变化，或者是被替换过的地址。
+const VOLATILE_KEYS: &[&str] = &["time", "start_time", "end_time", "ip", "user_ip"];
+
+/// 一次请求与其响应，凭据、响应中的 challenge 等令牌与地址已被隐去。
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Exchange {
+    pub what: String,
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    pub response: String,
+}
+
+impl Exchange {
+    fn matches(&self, what: &str, method: &str, url: &str, params: &[(String, String)]) -> bool {
+        self.what == what
+            && self.method == method
+            && strip_query(&self.url) == strip_query(url)
+            && request_params(&self.url, self.body.as_deref()) == params
+    }
+
+    fn into_response(self) -> Result<Response> {
+        let mut builder = http::Response::builder().status(self.status);
+        if let Some(location) = self.location {
+            builder = builder.header(LOCATION, location);
+        }
+        Ok(builder.body(self.response)?.into())
+    }
+}
+
+fn strip_query(url: &str) -> &str {
+    url.split_once('?').map(|(path, _)| path).unwrap_or(url)
+}
+
+/// 查询与表单参数，按名称排序，不稳定的值被清空。
+fn request_params(url: &str, body: Option<&str>) -> Vec<(String, String)> {
+    let query = Url::parse(url)
+        .map(|url| url.query().unwrap_or_default().to_string())
+        .unwrap_or_default();
+    let mut params = form_urlencoded::parse(query.as_bytes())
+        .chain(form_urlencoded::parse(body.unwrap_or_default().as_bytes()))
+        .map(|(k, v)| {
+            let v = if VOLATILE_KEYS.contains(&k.as_ref()) {
+                String::new()
+            } else {
+                v.into_owned()
+            };
+            (k.into_owned(), v)
+        })
+        .collect::<Vec<_>>();
+    params.sort();
+    params
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AddressKind {
+    V4,
+    V6,
+    Mac,
+}
+
+impl AddressKind {
+    fn parse(s: &str) -> Option<Self> {
+        if s.parse::<Ipv4Addr>().is_ok() {
+            Some(Self::V4)
+        } else if s.contains(':')
+            && s.contains(|c: char| c.is_ascii_digit())
+            && s.parse::<Ipv6Addr>().is_ok()
+        {
+            Some(Self::V6)
+        } else if is_mac(s) {
+            Some(Self::Mac)
+        } else {
+            None
+        }
+    }
+
+    /// 第 `i` 个替代地址，取自文档保留的地址段。
+    fn placeholder(self, i: usize, original: &str) -> String {
+        let i = i as u32 + 1;
+        match self {
+            Self::V4 => Ipv4Addr::from(u32::from(Ipv4Addr::new(192, 0, 2, 0)) + i).to_string(),
+            Self::V6 => {
+                Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, (i >> 16) as u16, i as u16).to_string()
+            }
+            Self::Mac => {
+                let sep = if original.contains('-') { "-" } else { ":" };
+                [0x02, 0, 0, (i >> 16) as u8, (i >> 8) as u8, i as u8]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join(sep)
+            }
+        }
+    }
+}
+
+fn is_mac(s: &str) -> bool {
+    let sep = match s.as_bytes().get(2) {
+        Some(b':') => ':',
+        Some(b'-') => '-',
+        _ => return false,
+    };
+    let parts = s.split(sep).collect::<Vec<_>>();
+    parts.len() == 6
+        && parts
+            .iter()
+            .all(|p| p.len() == 2 && p.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_address_char(c: char) -> bool {
+    c.is_ascii_hexdigit() || c == '.' || c == ':' || c == '-'
+}
+
+/// 已经替换的地址，同一个地址总是替换为同一个替代地址。
+#[derive(Debug, Default)]
+struct Addresses(Vec<(AddressKind, String)>);
+
+impl Addresses {
+    fn replace(&mut self, token: &str) -> Option<String> {
+        let kind = AddressKind::parse(token)?;
+        let pos = match self.0.iter().position(|(_, a)| a == token) {
+            Some(pos) => pos,
+            None => {
+                self.0.push((kind, token.to_string()));
+                self.0.len() - 1
+            }
+        };
+        let i = self.0[..pos].iter().filter(|(k, _)| *k == kind).count();
+        Some(kind.placeholder(i, token))
+    }
+
+    /// 把文本中的 IPv4、IPv6 与 MAC 地址替换为文档保留的地址。
+    fn scrub(&mut self, s: &str) -> String {
+        let mut scrubbed = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(start) = rest.find(is_address_char) {
+            scrubbed.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c| !is_address_char(c)).unwrap_or(rest.len());
+            let token = &rest[..end];
+            // 地址前后可能紧跟标点，如 "IP:1.2.3.4."。
+            let trimmed = token.trim_matches(|c| c == '.' || c == ':' || c == '-');
+            match self.replace(token) {
+                Some(a) => scrubbed.push_str(&a),
+                None => match self.replace(trimmed) {
+                    Some(a) => {
+                        let offset = token.find(trimmed).unwrap_or_default();
+                        scrubbed.push_str(&token[..offset]);
+                        scrubbed.push_str(&a);
+                        scrubbed.push_str(&token[offset + trimmed.len()..]);
+                    }
+                    None => scrubbed.push_str(token),
+                },
+            }
+            rest = &rest[end..];
+        }
+        scrubbed.push_str(rest);
+        scrubbed
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FixtureMode {
+    Record,
+    Replay,
+}
+
+/// 记录或回放门户的请求与响应。
+///
+/// 记录时每次请求后在夹具文件末尾追加一行 JSON，每次运行都会重新创建夹具文件；
+/// 回放时按请求的用途、方法、地址与参数依次匹配记录，不访问网络。用户名与 IP、
+/// MAC 地址在记录与回放时都会被替换，因此两者可以匹配。
+pub struct Fixtures {
+    mode: FixtureMode,
+    path: PathBuf,
+    secrets: Mutex<Vec<String>>,
+    addresses: Mutex<Addresses>,
+    exchanges: Mutex<Vec<Exchange>>,
+    file: Mutex<Option<File>>,
+}
+
+impl Fixtures {
+    fn new(mode: FixtureMode, path: PathBuf, exchanges: Vec<Exchange>) -> Self {
+        Self {
+            mode,
+            path,
+            secrets: Mutex::new(Vec::new()),
+            addresses: Mutex::new(Addresses::default()),
+            exchanges: Mutex::new(exchanges),
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::new(FixtureMode::Record, path.into(), Vec::new())
+    }
+
+    /// 读取夹具文件，每行一条记录，也接受旧版本的 JSON 数组。
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let text = std::fs::read_to_string(&path)?;
+        let exchanges = if text.trim_start().starts_with('[') {
+            serde_json::from_str(&text)?
+        } else {
+            text.lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        };
+        Ok(Self::new(FixtureMode::Replay, path, exchanges))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == FixtureMode::Replay
+    }
+
+    /// 记录时从响应中隐去的字符串，如用户名。
+    pub fn add_secret(&self, secret: &str) {
+        if !secret.is_empty() {
+            let mut secrets = self.secrets.lock().unwrap();
+            if !secrets.iter().any(|s| s == secret) {
+                secrets.push(secret.to_string());
+            }
+        }
+    }
+
+    fn scrub(&self, s: &str) -> String {
+        let s = self
+            .secrets
+            .lock()
+            .unwrap()
+            .iter()
+            .fold(s.to_string(), |s, secret| {
+                s.replace(secret.as_str(), REDACTED)
+            });
+        self.addresses.lock().unwrap().scrub(&s)
+    }
+
+    fn scrub_form(&self, body: &[u8]) -> String {
+        let pairs = redact_pairs(form_urlencoded::parse(body), SCRUB_KEYS);
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish();
+        self.scrub(&body)
+    }
+
+    pub(crate) async fn send(
+        &self,
+        what: &str,
+        client: &HttpClient,
+        req: Request,
+    ) -> Result<Response> {
+        let method = req.method().to_string();
+        let url = self.scrub(&redact_url_with(req.url(), SCRUB_KEYS));
+        let body = req
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| self.scrub_form(b));
+        match self.mode {
+            FixtureMode::Replay => {
+                let params = request_params(&url, body.as_deref());
+                let mut exchanges = self.exchanges.lock().unwrap();
+                let pos = exchanges
+                    .iter()
+                    .position(|e| e.matches(what, &method, &url, &params))
+                    .ok_or_else(|| {
+                        NetHelperError::NoFixture(format!("{} {} {}", what, method, url))
+                    })?;
+                tracing::debug!("回放记录的响应");
+                exchanges.remove(pos).into_response()
+            }
+            FixtureMode::Record => {
+                let res = client.execute(req).await?;
+                let status = res.status().as_u16();
+                let location = res
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|l| l.to_str().ok())
+                    .map(|l| l.to_string());
+                let response = res.text().await?;
+                let exchange = Exchange {
+                    what: what.to_string(),
+                    method,
+                    url,
+                    body,
+                    status,
+                    location,
+                    response,
+                };
+                self.save(&Exchange {
+                    response: self.scrub(&redact_json(&exchange.response, SCRUB_KEYS)),
+                    location: exchange.location.as_deref().map(|l| self.scrub(l)),
+                    ..exchange.clone()
+                })?;
+                exchange.into_response()
+            }
+        }
+    }
+
+    /// 在夹具文件末尾追加一条记录。本次运行第一次记录时创建文件，
+    /// 原有内容会被替换，以免与上一次记录的响应混在一起。
+    fn save(&self, exchange: &Exchange) -> Result<()> {
+        let mut file = self.file.lock().unwrap();
+        let file = match file.as_mut() {
+            Some(f) => f,
+            None => {
+                if let Some(p) = self.path.parent() {
+                    DirBuilder::new().recursive(true).create(p)?;
+                }
+                file.insert(File::create(&self.path)?)
+            }
+        };
+        let mut line = serde_json::to_string(exchange)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        tracing::debug!(path = %self.path.display(), "已记录响应");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrub_addresses() {
+        let mut addresses = Addresses::default();
+        let text = "IP:59.66.1.2, 59.66.1.3 mac 0C-9D-92-AA-BB-CC, \
+                    2402:f000:1:2::3. 59.66.1.2 at 2022-10-01 08:00:00, 1.23G";
+        assert_eq!(
+            addresses.scrub(text),
+            "IP:192.0.2.1, 192.0.2.2 mac 02-00-00-00-00-01, \
+             2001:db8::1. 192.0.2.1 at 2022-10-01 08:00:00, 1.23G"
+        );
+        assert_eq!(addresses.scrub("59.66.1.3"), "192.0.2.2");
+    }
+
+    #[test]
+    fn scrub_json() {
+        let text = r#"jQuery_1({"challenge":"0123abcd","client_ip":"59.66.1.2","error":"ok","res":"challenge"})"#;
+        assert_eq!(
+            redact_json(text, SCRUB_KEYS),
+            r#"jQuery_1({"challenge":"******","client_ip":"59.66.1.2","error":"ok","res":"challenge"})"#
+        );
+        assert_eq!(
+            redact_json(
+                r#"{"msg": "a\"b", "token" : "x\"y", "username":"zhangsan"}"#,
+                SCRUB_KEYS
+            ),
+            r#"{"msg": "a\"b", "token" : "******", "username":"******"}"#
+        );
+    }
+
+    #[test]
+    fn params() {
+        let a = request_params(
+            "http://usereg.tsinghua.edu.cn/user_detail_list.php?action=query&order=flux&start_time=2022-10-01",
+            None,
+        );
+        let b = request_params(
+            "http://usereg.tsinghua.edu.cn/user_detail_list.php?start_time=2022-11-01&order=flux&action=query",
+            None,
+        );
+        let c = request_params(
+            "http://usereg.tsinghua.edu.cn/user_detail_list.php?action=query&order=user_login_time&start_time=2022-10-01",
+            None,
+        );
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(
+            request_params("http://a/do.php", Some("action=drop&user_ip=192.0.2.1")),
+            request_params("http://a/do.php", Some("user_ip=192.0.2.2&action=drop")),
+        );
+    }
+}
@@ -3,51 +3,174 @@
 use async_trait::async_trait;
 use enum_dispatch::enum_dispatch;
 use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
+use zeroize::Zeroize;
 
 pub use anyhow::Result;
 pub use chrono::{
-    DateTime, Datelike, Duration as NaiveDuration, Local, NaiveDate, NaiveDateTime, Timelike,
+    DateTime, Datelike, Duration as NaiveDuration, FixedOffset, Local, NaiveDate, NaiveDateTime,
+    TimeZone, Timelike, Utc,
 };
 pub use reqwest::Client as HttpClient;
 
+#[cfg(not(any(feature = "net", feature = "auth")))]
+compile_error!("至少需要启用 net 或 auth 特性");
+
+mod ac_id;
+#[cfg(feature = "auth")]
 mod auth;
+pub mod billing;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod client;
+#[cfg(feature = "fixtures")]
+mod fixture;
+#[cfg(feature = "net")]
 mod net;
+#[cfg(feature = "auth")]
+mod portal;
+mod retry;
+mod trace;
+#[cfg(feature = "usereg")]
 pub mod usereg;
 
-pub use auth::{Auth4Connect, Auth6Connect};
+pub use ac_id::{AcIdCache, AcIdEntry};
+#[cfg(feature = "auth")]
+pub use auth::{decode_info, Auth4Connect, Auth6Connect};
+pub use client::HttpClientOptions;
+#[cfg(feature = "fixtures")]
+pub use fixture::{Exchange, Fixtures};
+#[cfg(feature = "net")]
 pub use net::NetConnect;
+#[cfg(feature = "auth")]
+pub use portal::{PortalConfig, PortalProfile};
+pub use retry::{RetryClass, RetryPolicy};
+#[cfg(feature = "auth")]
+pub use srun::{ChksumField, DEFAULT_ALPHABET};
+pub use trace::redact_url;
+
+/// 未启用 `fixtures` 特性时没有夹具，`with_fixtures` 只能传入 `None`。
+#[cfg(not(feature = "fixtures"))]
+pub enum Fixtures {}
+
+#[cfg(not(feature = "fixtures"))]
+impl Fixtures {
+    pub fn add_secret(&self, _secret: &str) {
+        match *self {}
+    }
+
+    pub(crate) async fn send(
+        &self,
+        _what: &str,
+        _client: &HttpClient,
+        _req: reqwest::Request,
+    ) -> Result<reqwest::Response> {
+        match *self {}
+    }
+}
 
 #[derive(Debug, Error)]
 enum NetHelperError {
-    #[error("无法获取 ac_id")]
-    NoAcId,
+    #[cfg(feature = "auth")]
+    #[error("无法获取 ac_id，已访问：{0}")]
+    NoAcId(String),
+    #[cfg(feature = "auth")]
     #[error("操作失败：{0}")]
     Log(String),
     #[error("登录状态异常")]
     NoFlux,
     #[error("无法识别的用户信息：{0}")]
     InvalidFlux(String),
+    #[cfg(feature = "usereg")]
     #[error("排序方式无效")]
     InvalidOrder,
     #[error("无法确定登录方式")]
     InvalidHost,
+    #[error("未启用该登录方式：{0:?}")]
+    DisabledHost(NetState),
+    #[error("该登录方式不支持指定 IP 或 MAC 地址")]
+    UnsupportedIp,
+    #[cfg(not(feature = "usereg"))]
+    #[error("未启用 usereg，无法使用代理")]
+    UnsupportedProxy,
+    #[cfg(feature = "auth")]
+    #[error("{0}")]
+    Srun(String),
+    #[error("无效的流量：{0}")]
+    InvalidFluxAmount(String),
+    #[error("无效的计费档位：{0}")]
+    InvalidTariff(String),
+    #[error("预测方式无效")]
+    InvalidProjection,
+    #[cfg(feature = "usereg")]
+    #[error("无效的时间：{0}")]
+    InvalidTime(String),
+    #[cfg(feature = "fixtures")]
+    #[error("回放文件中没有匹配的请求：{0}")]
+    NoFixture(String),
+}
+
+#[cfg(feature = "auth")]
+fn srun_error(e: srun::Error) -> anyhow::Error {
+    NetHelperError::Srun(e.to_string()).into()
+}
+
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(s: String) -> Self {
+        Self(s)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Secret {
+    fn from(s: String) -> Self {
+        Self::new(s)
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(******)")
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("******")
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct NetCredential {
     pub username: String,
-    pub password: String,
-    pub ac_ids: RwLock<Vec<i32>>,
+    pub password: Secret,
+    pub ac_ids: RwLock<AcIdCache>,
 }
 
 impl NetCredential {
-    pub fn new(username: String, password: String, ac_ids: Vec<i32>) -> Self {
+    pub fn new(username: String, password: impl Into<Secret>, ac_ids: AcIdCache) -> Self {
         Self {
             username,
-            password,
+            password: password.into(),
             ac_ids: RwLock::new(ac_ids),
         }
     }
@@ -90,19 +213,27 @@ impl Display for Flux {
     }
 }
 
+/// 解析如 `15G`、`1.5 GB`、`200M` 的流量，没有单位时为字节。
 impl std::str::FromStr for Flux {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self> {
-        let (flux, unit) = s.split_at(s.len() - 1);
-        Ok(Flux(
-            (flux.trim_end().parse::<f64>().unwrap_or_default()
-                * match unit {
-                    "G" => 1_000_000_000.0,
-                    "M" => 1_000_000.0,
-                    "K" => 1_000.0,
-                    _ => 1.0,
-                }) as u64,
-        ))
+        let invalid = || NetHelperError::InvalidFluxAmount(s.to_string());
+        let s = s.trim();
+        let (flux, unit) = s.split_at(s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len()));
+        let flux = flux
+            .trim_end()
+            .parse::<f64>()
+            .ok()
+            .filter(|f| f.is_finite() && *f >= 0.0)
+            .ok_or_else(invalid)?;
+        let unit = match unit.to_ascii_uppercase().as_str() {
+            "" | "B" => 1.0,
+            "K" | "KB" => 1_000.0,
+            "M" | "MB" => 1_000_000.0,
+            "G" | "GB" => 1_000_000_000.0,
+            _ => return Err(invalid().into()),
+        };
+        Ok(Flux((flux * unit) as u64))
     }
 }
 
@@ -171,6 +302,32 @@ impl std::str::FromStr for NetFlux {
     }
 }
 
+/// 校园网所在的时区，即 UTC+8。门户返回的时间都按此时区解释。
+pub fn campus_tz() -> FixedOffset {
+    FixedOffset::east_opt(8 * 3600).unwrap()
+}
+
+pub fn campus_now() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&campus_tz())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NetDetail {
+    pub login_time: DateTime<FixedOffset>,
+    pub logout_time: DateTime<FixedOffset>,
+    pub flux: Flux,
+}
+
+impl NetDetail {
+    pub fn from_detail(i: DateTime<FixedOffset>, o: DateTime<FixedOffset>, f: Flux) -> Self {
+        NetDetail {
+            login_time: i,
+            logout_time: o,
+            flux: f,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NetState {
     Unknown,
@@ -194,6 +351,17 @@ impl std::str::FromStr for NetState {
     }
 }
 
+impl std::fmt::Display for NetState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NetState::Unknown => "unknown",
+            NetState::Net => "net",
+            NetState::Auth4 => "auth4",
+            NetState::Auth6 => "auth6",
+        })
+    }
+}
+
 #[async_trait]
 #[enum_dispatch(TUNetConnect)]
 pub trait TUNetHelper: Send + Sync {
@@ -206,26 +374,84 @@ pub trait TUNetHelper: Send + Sync {
 #[enum_dispatch]
 #[derive(Clone)]
 pub enum TUNetConnect {
+    #[cfg(feature = "net")]
     NetConnect,
+    #[cfg(feature = "auth")]
     Auth4Connect,
+    #[cfg(feature = "auth")]
     Auth6Connect,
 }
 
 impl TUNetConnect {
     pub fn new(s: NetState, cred: Arc<NetCredential>, client: HttpClient) -> Result<TUNetConnect> {
         match s {
+            #[cfg(feature = "net")]
             NetState::Net => Ok(Self::NetConnect(net::NetConnect::new(cred, client))),
+            #[cfg(feature = "auth")]
             NetState::Auth4 => Ok(Self::Auth4Connect(auth::AuthConnect::new(cred, client))),
+            #[cfg(feature = "auth")]
             NetState::Auth6 => Ok(Self::Auth6Connect(auth::AuthConnect::new(cred, client))),
-            _ => Err(NetHelperError::InvalidHost.into()),
+            NetState::Unknown => Err(NetHelperError::InvalidHost.into()),
+            #[allow(unreachable_patterns)]
+            s => Err(NetHelperError::DisabledHost(s).into()),
+        }
+    }
+
+    pub fn with_ip(self, ip: Option<IpAddr>) -> Result<TUNetConnect> {
+        match self {
+            #[cfg(feature = "auth")]
+            Self::Auth4Connect(c) => Ok(Self::Auth4Connect(c.with_ip(ip))),
+            #[cfg(feature = "auth")]
+            Self::Auth6Connect(c) => Ok(Self::Auth6Connect(c.with_ip(ip))),
+            #[allow(unreachable_patterns)]
+            c if ip.is_none() => Ok(c),
+            #[allow(unreachable_patterns)]
+            _ => Err(NetHelperError::UnsupportedIp.into()),
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> TUNetConnect {
+        match self {
+            #[cfg(feature = "net")]
+            Self::NetConnect(c) => Self::NetConnect(c.with_retry(retry)),
+            #[cfg(feature = "auth")]
+            Self::Auth4Connect(c) => Self::Auth4Connect(c.with_retry(retry)),
+            #[cfg(feature = "auth")]
+            Self::Auth6Connect(c) => Self::Auth6Connect(c.with_retry(retry)),
+        }
+    }
+
+    pub fn with_fixtures(self, fixtures: Option<Arc<Fixtures>>) -> TUNetConnect {
+        match self {
+            #[cfg(feature = "net")]
+            Self::NetConnect(c) => Self::NetConnect(c.with_fixtures(fixtures)),
+            #[cfg(feature = "auth")]
+            Self::Auth4Connect(c) => Self::Auth4Connect(c.with_fixtures(fixtures)),
+            #[cfg(feature = "auth")]
+            Self::Auth6Connect(c) => Self::Auth6Connect(c.with_fixtures(fixtures)),
+        }
+    }
+
+    #[cfg(feature = "auth")]
+    pub fn with_portal(self, portal: Option<Arc<PortalConfig>>) -> TUNetConnect {
+        match (self, portal) {
+            (Self::Auth4Connect(c), Some(p)) => Self::Auth4Connect(c.with_portal(p.auth4.clone())),
+            (Self::Auth6Connect(c), Some(p)) => Self::Auth6Connect(c.with_portal(p.auth6.clone())),
+            (c, _) => c,
+        }
+    }
+
+    #[cfg(feature = "auth")]
+    pub async fn drop(&self, ip: IpAddr) -> Result<String> {
+        match self {
+            Self::Auth4Connect(c) => c.drop(ip).await,
+            Self::Auth6Connect(c) => c.drop(ip).await,
+            #[allow(unreachable_patterns)]
+            _ => Err(NetHelperError::UnsupportedIp.into()),
         }
     }
 }
 
 pub fn create_http_client() -> Result<HttpClient> {
-    Ok(reqwest::ClientBuilder::new()
-        .cookie_store(true)
-        .redirect(reqwest::redirect::Policy::none())
-        .no_proxy()
-        .build()?)
+    HttpClientOptions::default().build()
 }
@@ -6,6 +6,8 @@ use md5::{Digest, Md5};
 pub struct NetConnect {
     cred: Arc<NetCredential>,
     client: HttpClient,
+    retry: RetryPolicy,
+    fixtures: Option<Arc<Fixtures>>,
 }
 
 static NET_LOG_URI: &str = "http://net.tsinghua.edu.cn/do_login.php";
@@ -13,16 +15,35 @@ static NET_FLUX_URI: &str = "http://net.tsinghua.edu.cn/rad_user_info.php";
 
 impl NetConnect {
     pub fn new(cred: Arc<NetCredential>, client: HttpClient) -> Self {
-        NetConnect { cred, client }
+        NetConnect {
+            cred,
+            client,
+            retry: RetryPolicy::default(),
+            fixtures: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_fixtures(mut self, fixtures: Option<Arc<Fixtures>>) -> Self {
+        if let Some(f) = &fixtures {
+            f.add_secret(&self.cred.username);
+        }
+        self.fixtures = fixtures;
+        self
     }
 }
 
 #[async_trait]
 impl TUNetHelper for NetConnect {
+    #[tracing::instrument(name = "net_login", skip_all, fields(username = %self.cred.username))]
     async fn login(&self) -> Result<String> {
         let password_md5 = {
             let mut md5 = Md5::new();
-            md5.update(self.cred.password.as_bytes());
+            md5.update(self.cred.password.expose().as_bytes());
             md5.finalize()
         };
         let password_md5 = format!("{{MD5_HEX}}{}", HEXLOWER.encode(&password_md5));
@@ -32,19 +53,38 @@ impl TUNetHelper for NetConnect {
             ("username", &self.cred.username),
             ("password", &password_md5),
         ];
-        let res = self.client.post(NET_LOG_URI).form(&params).send().await?;
-        Ok(res.text().await?)
+        self.retry
+            .text(
+                "登录",
+                self.client.post(NET_LOG_URI).form(&params),
+                self.fixtures.as_deref(),
+            )
+            .await
     }
 
+    #[tracing::instrument(name = "net_logout", skip_all)]
     async fn logout(&self) -> Result<String> {
         let params = [("action", "logout")];
-        let res = self.client.post(NET_LOG_URI).form(&params).send().await?;
-        Ok(res.text().await?)
+        self.retry
+            .text(
+                "注销",
+                self.client.post(NET_LOG_URI).form(&params),
+                self.fixtures.as_deref(),
+            )
+            .await
     }
 
+    #[tracing::instrument(name = "net_flux", skip_all)]
     async fn flux(&self) -> Result<NetFlux> {
-        let res = self.client.get(NET_FLUX_URI).send().await?;
-        Ok(res.text().await?.parse()?)
+        let t = self
+            .retry
+            .text(
+                "获取在线状态",
+                self.client.get(NET_FLUX_URI),
+                self.fixtures.as_deref(),
+            )
+            .await?;
+        Ok(t.parse()?)
     }
 
     fn cred(&self) -> Arc<NetCredential> {
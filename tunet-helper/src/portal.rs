@@ -0,0 +1,149 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use srun::data_encoding::Encoding;
+use srun::{ChksumField, DEFAULT_ALPHABET, DEFAULT_CHKSUM};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// srun 认证门户的地址与登录参数。
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PortalProfile {
+    pub log_uri: String,
+    pub challenge_uri: String,
+    pub flux_uri: String,
+    pub redirect_uri: String,
+    pub drop_uri: String,
+    pub n: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub enc_ver: String,
+    pub double_stack: String,
+    pub alphabet: String,
+    pub chksum: Vec<ChksumField>,
+    pub extra: BTreeMap<String, String>,
+}
+
+impl PortalProfile {
+    pub fn with_host(host: &str, redirect_uri: &str) -> Self {
+        Self {
+            log_uri: format!("https://{}/cgi-bin/srun_portal", host),
+            challenge_uri: format!("https://{}/cgi-bin/get_challenge", host),
+            flux_uri: format!("https://{}/rad_user_info.php", host),
+            redirect_uri: redirect_uri.to_string(),
+            drop_uri: format!("https://{}/cgi-bin/rad_user_dm", host),
+            n: "200".to_string(),
+            ty: "1".to_string(),
+            enc_ver: "srun_bx1".to_string(),
+            double_stack: "1".to_string(),
+            alphabet: DEFAULT_ALPHABET.to_string(),
+            chksum: DEFAULT_CHKSUM.to_vec(),
+            extra: BTreeMap::new(),
+        }
+    }
+
+    pub fn auth4() -> Self {
+        Self::with_host("auth4.tsinghua.edu.cn", "http://3.3.3.3/")
+    }
+
+    pub fn auth6() -> Self {
+        Self::with_host("auth6.tsinghua.edu.cn", "http://[333::3]/")
+    }
+
+    pub fn encoding(&self) -> Result<Encoding> {
+        srun::encoding(&self.alphabet).map_err(srun_error)
+    }
+
+    /// 用 JSON 对象中的字段覆盖。
+    fn merge(self, fields: impl IntoIterator<Item = (String, JsonValue)>) -> Result<Self> {
+        let mut value = serde_json::to_value(self)?;
+        if let JsonValue::Object(map) = &mut value {
+            map.extend(fields);
+        }
+        let portal: Self = serde_json::from_value(value)?;
+        portal.encoding()?;
+        Ok(portal)
+    }
+}
+
+/// auth4 与 auth6 各自使用的门户。
+///
+/// 配置文件顶层的字段同时用于两者，`auth4` 与 `auth6` 对象中的字段只用于对应的门户；
+/// 未指定的字段使用对应门户的默认值。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortalConfig {
+    pub auth4: Arc<PortalProfile>,
+    pub auth6: Arc<PortalProfile>,
+}
+
+impl PortalConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_value(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn from_value(value: JsonValue) -> Result<Self> {
+        let mut map = match value {
+            JsonValue::Object(map) => map,
+            _ => return Err(anyhow::anyhow!("门户配置应为 JSON 对象")),
+        };
+        let mut host = |key: &str| match map.remove(key) {
+            Some(JsonValue::Object(map)) => Ok(map),
+            None => Ok(Default::default()),
+            Some(_) => Err(anyhow::anyhow!("门户配置中的 {} 应为 JSON 对象", key)),
+        };
+        let auth4 = host("auth4")?;
+        let auth6 = host("auth6")?;
+        Ok(Self {
+            auth4: Arc::new(PortalProfile::auth4().merge(map.clone().into_iter().chain(auth4))?),
+            auth6: Arc::new(PortalProfile::auth6().merge(map.into_iter().chain(auth6))?),
+        })
+    }
+}
+
+impl Default for PortalConfig {
+    fn default() -> Self {
+        Self {
+            auth4: Arc::new(PortalProfile::auth4()),
+            auth6: Arc::new(PortalProfile::auth6()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn defaults_per_host() {
+        let config = PortalConfig::from_value(json!({ "n": "100" })).unwrap();
+        assert_eq!(config.auth4.log_uri, PortalProfile::auth4().log_uri);
+        assert_eq!(config.auth6.log_uri, PortalProfile::auth6().log_uri);
+        assert_eq!(config.auth4.n, "100");
+        assert_eq!(config.auth6.n, "100");
+    }
+
+    #[test]
+    fn host_sections() {
+        let config = PortalConfig::from_value(json!({
+            "double_stack": "0",
+            "auth6": { "double_stack": "1", "redirect_uri": "http://[::1]/" },
+        }))
+        .unwrap();
+        assert_eq!(config.auth4.double_stack, "0");
+        assert_eq!(
+            config.auth4.redirect_uri,
+            PortalProfile::auth4().redirect_uri
+        );
+        assert_eq!(config.auth6.double_stack, "1");
+        assert_eq!(config.auth6.redirect_uri, "http://[::1]/");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(PortalConfig::from_value(json!([])).is_err());
+        assert!(PortalConfig::from_value(json!({ "auth4": "x" })).is_err());
+        assert!(PortalConfig::from_value(json!({ "alphabet": "abc" })).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::*;
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration as StdDuration, SystemTime, UNIX_EPOCH};
+
+/// 可以重试的错误类型。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryClass {
+    /// 无法建立连接或发送请求
+    Connect,
+    /// 连接或请求超时
+    Timeout,
+    /// 读取响应时连接中断
+    Body,
+}
+
+impl RetryClass {
+    fn matches(&self, e: &reqwest::Error) -> bool {
+        match self {
+            Self::Connect => e.is_connect() || e.is_request(),
+            Self::Timeout => e.is_timeout(),
+            Self::Body => e.is_body() || e.is_decode(),
+        }
+    }
+}
+
+/// 抖动只用于错开多个客户端的重试，不需要密码学强度，
+/// 因此用首次调用时的纳秒时间作种子的 xorshift 生成，并发时偶尔重复也无妨。
+fn jitter_random() -> u64 {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let mut x = STATE.load(Ordering::Relaxed);
+    if x == 0 {
+        x = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+            | 1;
+    }
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    x
+}
+
+/// 请求失败时的重试策略，延迟按指数增长，单位为毫秒。
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base_delay: u64,
+    pub max_delay: u64,
+    pub jitter: bool,
+    pub retry_on: Vec<RetryClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base_delay: 500,
+            max_delay: 4000,
+            jitter: true,
+            retry_on: vec![RetryClass::Connect, RetryClass::Timeout, RetryClass::Body],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    fn retryable(&self, e: &anyhow::Error) -> bool {
+        e.chain()
+            .filter_map(|e| e.downcast_ref::<reqwest::Error>())
+            .any(|e| self.retry_on.iter().any(|c| c.matches(e)))
+    }
+
+    /// 第 `attempt` 次失败后等待的时间。
+    pub fn delay(&self, attempt: u32) -> StdDuration {
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.max_delay);
+        let delay = if self.jitter && delay > 1 {
+            let random = jitter_random();
+            delay / 2 + random % (delay / 2)
+        } else {
+            delay
+        };
+        StdDuration::from_millis(delay)
+    }
+
+    pub async fn run<T, F, Fut>(&self, what: &str, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Err(e) if attempt < self.attempts && self.retryable(&e) => {
+                    let delay = self.delay(attempt);
+                    tracing::warn!(
+                        "{}失败，{} 毫秒后重试（{}/{}）：{}",
+                        what,
+                        delay.as_millis(),
+                        attempt,
+                        self.attempts - 1,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    pub(crate) async fn text(
+        &self,
+        what: &str,
+        req: RequestBuilder,
+        fixtures: Option<&Fixtures>,
+    ) -> Result<String> {
+        let req = &req;
+        self.run(what, || async move {
+            let req = req
+                .try_clone()
+                .ok_or_else(|| anyhow::anyhow!("无法重试该请求"))?;
+            Ok(trace::send(what, req, fixtures).await?.text().await?)
+        })
+        .await
+    }
+}
@@ -0,0 +1,127 @@
+use crate::*;
+use reqwest::{RequestBuilder, Response};
+use std::borrow::Cow;
+use tracing::Instrument;
+use url::Url;
+
+/// 这些参数的值不会出现在日志中。
+const SENSITIVE_KEYS: &[&str] = &[
+    "password",
+    "user_password",
+    "info",
+    "chksum",
+    "challenge",
+    "token",
+    "sign",
+];
+
+pub(crate) const REDACTED: &str = "******";
+
+fn is_sensitive(key: &str) -> bool {
+    SENSITIVE_KEYS.contains(&key)
+}
+
+/// 隐去敏感参数以及 `extra` 中参数的值。
+pub(crate) fn redact_pairs<'a>(
+    pairs: impl Iterator<Item = (Cow<'a, str>, Cow<'a, str>)>,
+    extra: &[&str],
+) -> Vec<(String, String)> {
+    pairs
+        .map(|(k, v)| {
+            let v = if is_sensitive(&k) || extra.contains(&k.as_ref()) {
+                REDACTED.into()
+            } else {
+                v.into_owned()
+            };
+            (k.into_owned(), v)
+        })
+        .collect()
+}
+
+/// 隐去 JSON（或 JSONP）文本中敏感字段与 `extra` 中字段的字符串值。
+#[cfg(feature = "fixtures")]
+pub(crate) fn redact_json(s: &str, extra: &[&str]) -> String {
+    // 字符串字面量的内容范围，引号与反斜杠都是 ASCII，按字节扫描不会切开字符。
+    let bytes = s.as_bytes();
+    let mut strings = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'"' {
+            let mut j = i + 1;
+            while j < bytes.len() && bytes[j] != b'"' {
+                j += if bytes[j] == b'\\' { 2 } else { 1 };
+            }
+            let end = j.min(bytes.len());
+            strings.push(i + 1..end);
+            i = end + 1;
+        } else {
+            i += 1;
+        }
+    }
+    let mut redacted = String::with_capacity(s.len());
+    let mut copied = 0;
+    for pair in strings.windows(2) {
+        let (key, value) = (&pair[0], &pair[1]);
+        let key_str = &s[key.clone()];
+        if value.start > key.end + 1
+            && s[key.end + 1..value.start - 1].trim() == ":"
+            && (is_sensitive(key_str) || extra.contains(&key_str))
+        {
+            redacted.push_str(&s[copied..value.start]);
+            redacted.push_str(REDACTED);
+            copied = value.end;
+        }
+    }
+    redacted.push_str(&s[copied..]);
+    redacted
+}
+
+pub(crate) fn redact_url_with(url: &Url, extra: &[&str]) -> String {
+    if url.query().is_none() {
+        return url.to_string();
+    }
+    let pairs = redact_pairs(url.query_pairs(), extra);
+    let mut url = url.clone();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url.to_string()
+}
+
+/// 隐去 URL 查询参数中的密码、令牌与校验值。
+pub fn redact_url(url: &Url) -> String {
+    redact_url_with(url, &[])
+}
+
+/// 发送请求，并在 `request` span 中记录去除敏感信息的 URL 与响应状态。
+pub(crate) async fn send(
+    what: &str,
+    req: RequestBuilder,
+    fixtures: Option<&Fixtures>,
+) -> Result<Response> {
+    let (client, req) = req.build_split();
+    let req = req?;
+    let span = tracing::debug_span!(
+        "request",
+        what,
+        method = %req.method(),
+        url = %redact_url(req.url()),
+    );
+    async move {
+        tracing::trace!("发送请求");
+        let res = match fixtures {
+            Some(fixtures) => fixtures.send(what, &client, req).await,
+            None => client.execute(req).await.map_err(Into::into),
+        };
+        match res {
+            Ok(res) => {
+                tracing::debug!(status = %res.status(), "收到响应");
+                Ok(res)
+            }
+            Err(e) => {
+                tracing::debug!(error = %e, "请求失败");
+                Err(e)
+            }
+        }
+    }
+    .instrument(span)
+    .await
+}
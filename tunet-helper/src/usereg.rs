@@ -1,6 +1,5 @@
 use crate::*;
 use async_stream::try_stream;
-use chrono::Local;
 use data_encoding::HEXLOWER;
 use futures_core::Stream;
 use mac_address::MacAddress;
@@ -10,16 +9,23 @@ use select::predicate::*;
 use std::net::Ipv4Addr;
 use url::Url;
 
+pub use crate::NetDetail;
+
 #[derive(Debug, Clone, Copy)]
 pub struct NetUser {
     pub address: Ipv4Addr,
-    pub login_time: NaiveDateTime,
+    pub login_time: DateTime<FixedOffset>,
     pub mac_address: Option<MacAddress>,
     pub flux: Flux,
 }
 
 impl NetUser {
-    pub fn from_detail(a: Ipv4Addr, t: NaiveDateTime, m: Option<MacAddress>, f: Flux) -> Self {
+    pub fn from_detail(
+        a: Ipv4Addr,
+        t: DateTime<FixedOffset>,
+        m: Option<MacAddress>,
+        f: Flux,
+    ) -> Self {
         NetUser {
             address: a,
             login_time: t,
@@ -29,23 +35,6 @@ impl NetUser {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct NetDetail {
-    pub login_time: NaiveDateTime,
-    pub logout_time: NaiveDateTime,
-    pub flux: Flux,
-}
-
-impl NetDetail {
-    pub fn from_detail(i: NaiveDateTime, o: NaiveDateTime, f: Flux) -> Self {
-        NetDetail {
-            login_time: i,
-            logout_time: o,
-            flux: f,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Copy)]
 pub enum NetDetailOrder {
     LoginTime,
@@ -82,8 +71,11 @@ impl std::str::FromStr for NetDetailOrder {
 pub struct UseregHelper {
     cred: Arc<NetCredential>,
     client: HttpClient,
+    retry: RetryPolicy,
+    fixtures: Option<Arc<Fixtures>>,
 }
 
+static USEREG_HOST: &str = "usereg.tsinghua.edu.cn";
 // Use HTTP because TLS1.0/1.1 aren't supported.
 static USEREG_LOG_URI: &str = "http://usereg.tsinghua.edu.cn/do.php";
 static USEREG_INFO_URI: &str = "http://usereg.tsinghua.edu.cn/online_user_ipv4.php";
@@ -92,15 +84,52 @@ static USEREG_DETAIL_URI: &str = "http://usereg.tsinghua.edu.cn/user_detail_list
 static DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 const USEREG_OFF: usize = 1000;
 
+fn parse_time(s: &str) -> Result<DateTime<FixedOffset>> {
+    let invalid = || NetHelperError::InvalidTime(s.to_string());
+    let t = NaiveDateTime::parse_from_str(s.trim(), DATE_TIME_FORMAT).map_err(|_| invalid())?;
+    Ok(campus_tz()
+        .from_local_datetime(&t)
+        .single()
+        .ok_or_else(invalid)?)
+}
+
+/// 只用于访问 usereg 的代理。
+pub(crate) fn proxy(uri: &str) -> Result<reqwest::Proxy> {
+    reqwest::Proxy::all(uri)?;
+    let uri = Url::parse(uri)?;
+    Ok(reqwest::Proxy::custom(move |url| {
+        (url.host_str() == Some(USEREG_HOST)).then(|| uri.clone())
+    }))
+}
+
 impl UseregHelper {
     pub fn new(cred: Arc<NetCredential>, client: HttpClient) -> Self {
-        UseregHelper { cred, client }
+        UseregHelper {
+            cred,
+            client,
+            retry: RetryPolicy::default(),
+            fixtures: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_fixtures(mut self, fixtures: Option<Arc<Fixtures>>) -> Self {
+        if let Some(f) = &fixtures {
+            f.add_secret(&self.cred.username);
+        }
+        self.fixtures = fixtures;
+        self
     }
 
+    #[tracing::instrument(name = "usereg_login", skip_all, fields(username = %self.cred.username))]
     pub async fn login(&self) -> Result<String> {
         let password_md5 = {
             let mut md5 = Md5::new();
-            md5.update(self.cred.password.as_bytes());
+            md5.update(self.cred.password.expose().as_bytes());
             md5.finalize()
         };
         let params = [
@@ -108,30 +137,32 @@ impl UseregHelper {
             ("user_login_name", &self.cred.username),
             ("user_password", &HEXLOWER.encode(&password_md5)),
         ];
-        let res = self
-            .client
-            .post(USEREG_LOG_URI)
-            .form(&params)
-            .send()
-            .await?;
-        Ok(res.text().await?)
+        self.retry
+            .text(
+                "登录用户管理页面",
+                self.client.post(USEREG_LOG_URI).form(&params),
+                self.fixtures.as_deref(),
+            )
+            .await
     }
 
+    #[tracing::instrument(name = "usereg_logout", skip_all)]
     pub async fn logout(&self) -> Result<String> {
         let params = [("action", "logout")];
-        let res = self
-            .client
-            .post(USEREG_LOG_URI)
-            .form(&params)
-            .send()
-            .await?;
-        Ok(res.text().await?)
+        self.retry
+            .text(
+                "注销用户管理页面",
+                self.client.post(USEREG_LOG_URI).form(&params),
+                self.fixtures.as_deref(),
+            )
+            .await
     }
 
     pub fn cred(&self) -> Arc<NetCredential> {
         self.cred.clone()
     }
 
+    #[tracing::instrument(name = "usereg_connect", skip(self))]
     pub async fn connect(&self, addr: Ipv4Addr) -> Result<String> {
         let params = [
             ("n", "100"),
@@ -141,32 +172,35 @@ impl UseregHelper {
             ("user_ip", &addr.to_string()),
             ("drop", "0"),
         ];
-        let res = self
-            .client
-            .post(USEREG_CONNECT_URI)
-            .form(&params)
-            .send()
-            .await?;
-        Ok(res.text().await?)
+        self.retry
+            .text(
+                "上线 IP",
+                self.client.post(USEREG_CONNECT_URI).form(&params),
+                self.fixtures.as_deref(),
+            )
+            .await
     }
 
+    #[tracing::instrument(name = "usereg_drop", skip(self))]
     pub async fn drop(&self, addr: Ipv4Addr) -> Result<String> {
         let params = [("action", "drop"), ("user_ip", &addr.to_string())];
-        let res = self
-            .client
-            .post(USEREG_INFO_URI)
-            .form(&params)
-            .send()
-            .await?;
-        Ok(res.text().await?)
+        self.retry
+            .text(
+                "下线 IP",
+                self.client.post(USEREG_INFO_URI).form(&params),
+                self.fixtures.as_deref(),
+            )
+            .await
     }
 
     pub fn users(&self) -> impl Stream<Item = Result<NetUser>> {
         let client = self.client.clone();
+        let retry = self.retry.clone();
+        let fixtures = self.fixtures.clone();
         try_stream! {
-            let res = client.get(USEREG_INFO_URI).send().await?;
+            let text = retry.text("查询在线 IP", client.get(USEREG_INFO_URI), fixtures.as_deref()).await?;
             let doc = {
-                let doc = Document::from(res.text().await?.as_str());
+                let doc = Document::from(text.as_str());
                 doc
                     .find(Name("tr").descendant(Attr("align", "center")))
                     .skip(1)
@@ -178,8 +212,7 @@ impl UseregHelper {
                     tds[0]
                         .parse()
                         .unwrap_or_else(|_| Ipv4Addr::new(0, 0, 0, 0)),
-                    NaiveDateTime::parse_from_str(&tds[1], DATE_TIME_FORMAT)
-                        .unwrap_or_else(|_| NaiveDateTime::from_timestamp(0, 0)),
+                    parse_time(&tds[1])?,
                     tds[6].parse().ok(),
                     tds[2].parse().unwrap_or_default(),
                 );
@@ -189,7 +222,9 @@ impl UseregHelper {
 
     pub fn details(&self, o: NetDetailOrder, des: bool) -> impl Stream<Item = Result<NetDetail>> {
         let client = self.client.clone();
-        let now = Local::now();
+        let retry = self.retry.clone();
+        let fixtures = self.fixtures.clone();
+        let now = campus_now();
         let start_time = now.format("%Y-%m-01").to_string();
         let end_time = now.format("%Y-%m-%d").to_string();
         let des = if des { "DESC" } else { "" };
@@ -208,9 +243,9 @@ impl UseregHelper {
                     ],
                 )
                 .unwrap();
-                let res = client.get(uri).send().await?;
+                let text = retry.text("查询流量明细", client.get(uri), fixtures.as_deref()).await?;
                 let doc = {
-                    let doc = Document::from(res.text().await?.as_str());
+                    let doc = Document::from(text.as_str());
                     doc
                         .find(Name("tr").descendant(Attr("align", "center")))
                         .skip(1)
@@ -221,10 +256,8 @@ impl UseregHelper {
                 for tds in doc {
                     if !tds.is_empty() {
                         yield NetDetail::from_detail(
-                            NaiveDateTime::parse_from_str(&tds[1], DATE_TIME_FORMAT)
-                                .unwrap_or_else(|_| NaiveDateTime::from_timestamp(0, 0)),
-                            NaiveDateTime::parse_from_str(&tds[2], DATE_TIME_FORMAT)
-                                .unwrap_or_else(|_| NaiveDateTime::from_timestamp(0, 0)),
+                            parse_time(&tds[1])?,
+                            parse_time(&tds[2])?,
                             tds[4].parse().unwrap_or_default(),
                         );
                         new_len += 1;
@@ -237,3 +270,16 @@ impl UseregHelper {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time() {
+        let t = parse_time("2022-10-01 08:00:00").unwrap();
+        assert_eq!(t.to_rfc3339(), "2022-10-01T08:00:00+08:00");
+        assert!(parse_time("").is_err());
+        assert!(parse_time("2022-13-01 08:00:00").is_err());
+    }
+}
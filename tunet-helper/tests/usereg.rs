@@ -0,0 +1,67 @@
+use futures_util::TryStreamExt;
+use std::sync::Arc;
+use tunet_helper::{usereg::*, *};
+
+fn helper() -> UseregHelper {
+    let fixtures = Fixtures::replay(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/usereg.jsonl"
+    ))
+    .unwrap();
+    let cred = NetCredential::new(
+        "zhangsan20".to_string(),
+        String::new(),
+        AcIdCache::default(),
+    );
+    UseregHelper::new(Arc::new(cred), create_http_client().unwrap())
+        .with_fixtures(Some(Arc::new(fixtures)))
+}
+
+fn time(s: &str) -> DateTime<FixedOffset> {
+    campus_tz()
+        .from_local_datetime(&NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap())
+        .unwrap()
+}
+
+fn flux(s: &str) -> Flux {
+    s.parse().unwrap()
+}
+
+#[tokio::test]
+async fn users() {
+    let users = helper().users().try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(users.len(), 2);
+    assert_eq!(users[0].address.to_string(), "192.0.2.1");
+    assert_eq!(users[0].login_time, time("2022-10-01 08:00:00"));
+    assert_eq!(users[0].flux, flux("1.5G"));
+    assert_eq!(
+        users[0].mac_address.map(|m| m.to_string().to_lowercase()),
+        Some("02:00:00:00:00:01".to_string())
+    );
+    assert_eq!(users[1].address.to_string(), "192.0.2.2");
+    assert_eq!(users[1].flux, flux("200M"));
+}
+
+#[tokio::test]
+async fn details() {
+    let helper = helper();
+    // 按参数匹配，与记录的先后顺序无关。
+    let by_login = helper
+        .details(NetDetailOrder::LoginTime, false)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(by_login.len(), 2);
+    assert_eq!(by_login[0].login_time, time("2022-10-01 08:00:00"));
+    assert_eq!(by_login[0].logout_time, time("2022-10-01 12:00:00"));
+    assert_eq!(by_login[0].flux, flux("300M"));
+    let by_flux = helper
+        .details(NetDetailOrder::Flux, true)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(by_flux[0].flux, flux("1.2G"));
+    assert_eq!(by_flux[1].flux, flux("300M"));
+    assert!(helper.users().try_collect::<Vec<_>>().await.is_ok());
+    assert!(helper.users().try_collect::<Vec<_>>().await.is_err());
+}
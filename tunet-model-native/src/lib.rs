@@ -11,7 +11,9 @@ use tunet_helper::*;
 use tunet_model::*;
 use tunet_settings::*;
 use widestring::{U16CStr, U16CString};
+use zeroize::Zeroize;
 
+mod log;
 mod native;
 
 unsafe fn write_str(p: *const u16) -> String {
@@ -91,6 +93,11 @@ fn tunet_model_start_impl(
         if let Some(main) = main {
             let (tx, mut rx) = channel(32);
             let model = Arc::new(RwLock::new(Model::new(tx)?));
+            {
+                let model = model.read().unwrap();
+                let reader = FileSettingsReader::with_profile(model.profile.as_deref())?;
+                model.queue(Action::Preferences(Box::new(reader.read_preferences())));
+            }
             {
                 let model = model.clone();
                 tokio::spawn(async move {
@@ -101,7 +108,8 @@ fn tunet_model_start_impl(
                 });
             }
             let res = main(Arc::as_ptr(&model), data);
-            let mut reader = FileSettingsReader::new()?;
+            let mut reader =
+                FileSettingsReader::with_profile(model.read().unwrap().profile.as_deref())?;
             if model.read().unwrap().del_at_exit() {
                 reader.delete()?;
             } else {
@@ -114,6 +122,15 @@ fn tunet_model_start_impl(
     })
 }
 
+#[no_mangle]
+pub extern "C" fn tunet_log_init(
+    level: native::LogLevel,
+    f: native::LogCallback,
+    data: *mut c_void,
+) -> bool {
+    log::init(level, f, data)
+}
+
 #[no_mangle]
 pub extern "C" fn tunet_model_start(
     val: usize,
@@ -138,7 +155,7 @@ pub unsafe extern "C" fn tunet_model_queue(model: native::Model, action: native:
 }
 
 unsafe fn tunet_model_queue_cred_load_impl(model: native::Model) -> Result<()> {
-    let reader = FileSettingsReader::new()?;
+    let reader = FileSettingsReader::with_profile(read_model(model).profile.as_deref())?;
     let cred = reader.read_with_password()?;
     read_model(model).queue(Action::Credential(Arc::new(cred)));
     Ok(())
@@ -155,7 +172,41 @@ pub unsafe extern "C" fn tunet_model_queue_cred(
     u: *const u16,
     p: *const u16,
 ) {
-    read_model(model).queue(Action::UpdateCredential(write_str(u), write_str(p)));
+    read_model(model).queue(Action::UpdateCredential(write_str(u), write_str(p).into()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn tunet_model_queue_profile(model: native::Model, p: *const u16) {
+    let p = write_str(p);
+    read_model(model).queue(Action::Profile(if p.is_empty() { None } else { Some(p) }));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn tunet_model_profile(
+    model: native::Model,
+    f: native::StringCallback,
+    data: *mut c_void,
+) {
+    if let Some(f) = f {
+        read_str(
+            read_model(model).profile.as_deref().unwrap_or_default(),
+            f,
+            data,
+        )
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn tunet_model_profiles(f: native::StringCallback, data: *mut c_void) {
+    if let Some(f) = f {
+        read_str(
+            &FileSettingsReader::profiles()
+                .unwrap_or_default()
+                .join("\n"),
+            f,
+            data,
+        )
+    }
 }
 
 #[no_mangle]
@@ -207,7 +258,11 @@ pub unsafe extern "C" fn tunet_model_cred_password(
     data: *mut c_void,
 ) {
     if let Some(f) = f {
-        read_str(&read_model(model).cred.password, f, data)
+        let mut u16str =
+            U16CString::from_str_unchecked(read_model(model).cred.password.expose()).into_vec();
+        u16str.push(0);
+        f(u16str.as_ptr(), data);
+        u16str.zeroize();
     }
 }
 
@@ -253,6 +308,30 @@ pub unsafe extern "C" fn tunet_model_flux_balance(model: native::Model) -> f64 {
     read_model(model).flux.balance.0
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn tunet_model_alerts(
+    model: native::Model,
+    f: native::StringCallback,
+    data: *mut c_void,
+) {
+    if let Some(f) = f {
+        read_str(
+            &read_model(model)
+                .alerts
+                .iter()
+                .map(|a| a.to_string())
+                .join("\n"),
+            f,
+            data,
+        )
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn tunet_model_estimate(model: native::Model) -> native::Estimate {
+    read_model(model).estimate().into()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn tunet_model_onlines_foreach(
     model: native::Model,
@@ -296,12 +375,15 @@ pub unsafe extern "C" fn tunet_model_details_grouped_foreach(
         for (date, flux) in read_model(model)
             .details
             .iter()
-            .group_by(|detail| detail.logout_time.date())
+            .group_by(|detail| detail.logout_time.naive_local().date())
             .into_iter()
             .map(|(key, group)| (key, group.map(|detail| detail.flux.0).sum::<u64>()))
         {
             let g = native::DetailGroup {
-                logout_date: date.and_hms(0, 0, 0).timestamp(),
+                logout_date: campus_tz()
+                    .from_local_datetime(&date.and_hms(0, 0, 0))
+                    .unwrap()
+                    .timestamp(),
                 flux,
             };
             if !f(&g, data) {
@@ -0,0 +1,96 @@
+use crate::native::{self, LogLevel};
+use std::ffi::c_void;
+use std::io::{Result, Write};
+use tracing::Metadata;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use widestring::U16CString;
+
+static LOG_TARGETS: &[&str] = &[
+    "tunet_helper",
+    "tunet_suggest",
+    "tunet_settings",
+    "tunet_model",
+    "tunet_model_native",
+];
+
+#[derive(Clone, Copy)]
+struct LogSink {
+    func: extern "C" fn(LogLevel, *const u16, *mut c_void),
+    data: *mut c_void,
+}
+
+unsafe impl Send for LogSink {}
+unsafe impl Sync for LogSink {}
+
+/// 缓存一条日志，在析构时整行交给回调。
+struct LogLine {
+    sink: LogSink,
+    level: LogLevel,
+    buffer: Vec<u8>,
+}
+
+impl Write for LogLine {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogLine {
+    fn drop(&mut self) {
+        let line = String::from_utf8_lossy(&self.buffer);
+        let line = line.trim_end();
+        if !line.is_empty() {
+            let line = U16CString::from_str_truncate(line);
+            (self.sink.func)(self.level, line.as_ptr(), self.sink.data);
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogSink {
+    type Writer = LogLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.make_line(LogLevel::Info)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.make_line(meta.level().into())
+    }
+}
+
+impl LogSink {
+    fn make_line(&self, level: LogLevel) -> LogLine {
+        LogLine {
+            sink: *self,
+            level,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+pub fn init(level: LogLevel, func: native::LogCallback, data: *mut c_void) -> bool {
+    let func = match func {
+        Some(func) => func,
+        None => return false,
+    };
+    let level = LevelFilter::from(level);
+    let filter = Targets::new()
+        .with_targets(LOG_TARGETS.iter().map(|t| (*t, level)))
+        .with_default(LevelFilter::WARN.min(level));
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .without_time()
+        .with_writer(LogSink { func, data });
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layer)
+        .try_init()
+        .is_ok()
+}
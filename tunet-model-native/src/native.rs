@@ -3,7 +3,10 @@ use std::{
     ffi::c_void,
     sync::{Arc, RwLock},
 };
+use tracing::Level;
+use tracing_subscriber::filter::LevelFilter;
 use tunet_helper::{
+    billing,
     usereg::{NetDetail, NetUser},
     NetState,
 };
@@ -67,6 +70,40 @@ impl From<Option<NetState>> for State {
     }
 }
 
+#[repr(i32)]
+#[derive(Debug, Clone, Copy)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(l: LogLevel) -> Self {
+        match l {
+            LogLevel::Error => Self::ERROR,
+            LogLevel::Warn => Self::WARN,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Debug => Self::DEBUG,
+            LogLevel::Trace => Self::TRACE,
+        }
+    }
+}
+
+impl From<&Level> for LogLevel {
+    fn from(l: &Level) -> Self {
+        match *l {
+            Level::ERROR => Self::Error,
+            Level::WARN => Self::Warn,
+            Level::INFO => Self::Info,
+            Level::DEBUG => Self::Debug,
+            Level::TRACE => Self::Trace,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct OnlineUser {
     pub address: u32,
@@ -109,6 +146,23 @@ impl From<&NetDetail> for Detail {
     }
 }
 
+#[repr(C)]
+pub struct Estimate {
+    pub charge: f64,
+    pub projected_flux: u64,
+    pub projected_charge: f64,
+}
+
+impl From<billing::Estimate> for Estimate {
+    fn from(e: billing::Estimate) -> Self {
+        Self {
+            charge: e.charge.0,
+            projected_flux: e.projected_flux.0,
+            projected_charge: e.projected_charge.0,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct DetailGroup {
     pub logout_date: i64,
@@ -124,6 +178,7 @@ pub struct DetailGroupByTime {
 pub type MainCallback = Option<extern "C" fn(Model, *mut c_void) -> i32>;
 pub type UpdateCallback = Option<extern "C" fn(UpdateMsg, *mut c_void)>;
 pub type StringCallback = Option<extern "C" fn(*const u16, *mut c_void)>;
+pub type LogCallback = Option<extern "C" fn(LogLevel, *const u16, *mut c_void)>;
 pub type OnlinesForeachCallback = Option<extern "C" fn(*const OnlineUser, *mut c_void) -> bool>;
 pub type DetailsForeachCallback = Option<extern "C" fn(*const Detail, *mut c_void) -> bool>;
 pub type DetailsGroupedForeachCallback =
@@ -0,0 +1,121 @@
+use std::fmt::{Display, Formatter};
+use tunet_helper::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thresholds {
+    pub flux: Vec<Flux>,
+    pub balance: Option<Balance>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            flux: vec![Flux::from_gb(15.0), Flux::from_gb(20.0)],
+            balance: Some(Balance(5.0)),
+        }
+    }
+}
+
+impl Thresholds {
+    /// 从 `old` 到 `new` 越过的阈值。
+    ///
+    /// 第一次读取或用户改变时只作为比较的起点，不提醒。
+    pub fn crossed(&self, old: &NetFlux, new: &NetFlux) -> Vec<Alert> {
+        if new.username.is_empty() || old.username != new.username {
+            return Vec::new();
+        }
+        let mut alerts = self
+            .flux
+            .iter()
+            .filter(|t| old.flux < **t && new.flux >= **t)
+            .map(|t| Alert::Flux(*t))
+            .collect::<Vec<_>>();
+        if let Some(t) = self.balance {
+            if old.balance >= t && new.balance < t {
+                alerts.push(Alert::Balance(t));
+            }
+        }
+        alerts
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alert {
+    Flux(Flux),
+    Balance(Balance),
+}
+
+impl Display for Alert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Flux(t) => write!(f, "流量已超过 {}", t),
+            Self::Balance(t) => write!(f, "余额已低于 {}", t),
+        }
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        pub fn notify(alerts: &[Alert]) -> Result<()> {
+            let body = alerts.iter().map(|a| a.to_string()).collect::<Vec<_>>().join("\n");
+            notify_rust::Notification::new()
+                .appname("tunet")
+                .summary("清华大学校园网")
+                .body(&body)
+                .show()?;
+            Ok(())
+        }
+    } else {
+        pub fn notify(_alerts: &[Alert]) -> Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flux(username: &str, gb: f64, balance: f64) -> NetFlux {
+        NetFlux {
+            username: username.to_string(),
+            flux: Flux::from_gb(gb),
+            balance: Balance(balance),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn first_reading() {
+        let t = Thresholds::default();
+        assert!(t
+            .crossed(&NetFlux::default(), &flux("a", 30.0, 1.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn crossing() {
+        let t = Thresholds::default();
+        assert_eq!(
+            t.crossed(&flux("a", 14.0, 6.0), &flux("a", 16.0, 4.0)),
+            [
+                Alert::Flux(Flux::from_gb(15.0)),
+                Alert::Balance(Balance(5.0))
+            ]
+        );
+        assert!(t
+            .crossed(&flux("a", 16.0, 4.0), &flux("a", 17.0, 3.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn user_changed() {
+        let t = Thresholds::default();
+        assert!(t
+            .crossed(&flux("a", 1.0, 10.0), &flux("b", 30.0, 1.0))
+            .is_empty());
+        assert!(t
+            .crossed(&flux("a", 1.0, 10.0), &NetFlux::default())
+            .is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use serde_json::Value as JsonValue;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tunet_helper::*;
+
+/// 传给命令钩子的环境变量，其余的（如 `TUNET_PASSWORD`）都不会传入。
+const HOOK_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "TMPDIR",
+    "SystemRoot",
+    "ComSpec",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "USERPROFILE",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    LoginSucceeded,
+    LoginFailed,
+    SessionDropped,
+    ThresholdCrossed,
+    NetworkChanged,
+}
+
+impl HookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LoginSucceeded => "login",
+            Self::LoginFailed => "login-failed",
+            Self::SessionDropped => "dropped",
+            Self::ThresholdCrossed => "alert",
+            Self::NetworkChanged => "network",
+        }
+    }
+}
+
+impl std::str::FromStr for HookEvent {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            Self::LoginSucceeded,
+            Self::LoginFailed,
+            Self::SessionDropped,
+            Self::ThresholdCrossed,
+            Self::NetworkChanged,
+        ]
+        .into_iter()
+        .find(|e| s.eq_ignore_ascii_case(e.name()))
+        .ok_or_else(|| anyhow::anyhow!("无法识别的事件：{}", s))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookTarget {
+    Command(String),
+    Webhook(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub event: HookEvent,
+    pub target: HookTarget,
+}
+
+impl std::str::FromStr for Hook {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let (event, target) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("钩子格式应为 事件=命令：{}", s))?;
+        let target = target.trim();
+        let target = if target.starts_with("http://") || target.starts_with("https://") {
+            HookTarget::Webhook(target.to_string())
+        } else {
+            HookTarget::Command(target.to_string())
+        };
+        Ok(Self {
+            event: event.trim().parse()?,
+            target,
+        })
+    }
+}
+
+impl Hook {
+    pub async fn run(&self, payload: &JsonValue, http: &HttpClient) -> Result<()> {
+        match &self.target {
+            HookTarget::Command(cmd) => {
+                let mut command = if cfg!(windows) {
+                    let mut c = Command::new("cmd");
+                    c.arg("/C").arg(cmd);
+                    c
+                } else {
+                    let mut c = Command::new("sh");
+                    c.arg("-c").arg(cmd);
+                    c
+                };
+                command.env_clear();
+                for key in HOOK_ENV {
+                    if let Some(value) = std::env::var_os(key) {
+                        command.env(key, value);
+                    }
+                }
+                if let JsonValue::Object(map) = payload {
+                    for (key, value) in map {
+                        let value = match value {
+                            JsonValue::String(s) => s.clone(),
+                            JsonValue::Null => String::new(),
+                            v => v.to_string(),
+                        };
+                        command.env(format!("TUNET_{}", key.to_ascii_uppercase()), value);
+                    }
+                }
+                // 标准错误不能直接输出，否则会打乱 CUI 的界面。
+                let mut child = command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // 命令可能不读取标准输入就退出。
+                    match stdin.write_all(payload.to_string().as_bytes()).await {
+                        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => {
+                            return Err(e.into())
+                        }
+                        _ => {}
+                    }
+                }
+                let output = child.wait_with_output().await?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !stderr.trim().is_empty() {
+                    tracing::debug!(hook = %cmd, "{}", stderr.trim_end());
+                }
+                if !output.status.success() {
+                    return Err(anyhow::anyhow!("命令退出：{}", output.status));
+                }
+            }
+            HookTarget::Webhook(url) => {
+                // 地址中可能带有令牌，不放进错误信息。
+                http.post(url)
+                    .json(payload)
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(|e| e.without_url())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn command_env() {
+        let path = std::env::temp_dir().join(format!("tunet-hook-env-{}", std::process::id()));
+        std::env::set_var("TUNET_PASSWORD", "hook-secret-password");
+        std::env::set_var("TUNET_PASSPHRASE", "hook-secret-passphrase");
+        let hook: Hook = format!("login=env > '{}'", path.display()).parse().unwrap();
+        let payload = serde_json::json!({ "event": "login", "username": "u" });
+        hook.run(&payload, &create_http_client().unwrap())
+            .await
+            .unwrap();
+        let env = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(!env.contains("hook-secret"));
+        assert!(env.contains("TUNET_USERNAME=u"));
+        assert!(env.contains("PATH="));
+    }
+
+    #[tokio::test]
+    async fn ignores_stdin() {
+        let hook: Hook = "login=exit 0".parse().unwrap();
+        let payload = serde_json::json!({ "event": "login", "data": "x".repeat(100_000) });
+        hook.run(&payload, &create_http_client().unwrap())
+            .await
+            .unwrap();
+        let hook: Hook = "login=echo failed >&2; exit 3".parse().unwrap();
+        assert!(hook
+            .run(&payload, &create_http_client().unwrap())
+            .await
+            .is_err());
+    }
+}
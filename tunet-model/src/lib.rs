@@ -5,33 +5,63 @@ use drop_guard::guard;
 use futures_util::{pin_mut, TryStreamExt};
 use mac_address::*;
 use netstatus::*;
+use serde_json::json;
 use std::borrow::Cow;
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
 use tokio::sync::mpsc::*;
-use tunet_helper::{usereg::*, *};
+use tracing::Instrument;
+use tunet_helper::{billing::*, usereg::*, *};
+use tunet_settings::{FileSettingsReader, Preferences};
 use tunet_suggest as suggest;
 
+mod alert;
+mod hook;
+
+pub use alert::*;
+pub use hook::*;
+
+/// 检查网络是否改变的间隔秒数。
+const STATUS_INTERVAL: u64 = 10;
+const HOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 pub type UpdateCallback = Arc<dyn Fn(UpdateMsg) + Send + Sync + 'static>;
 
 pub struct Model {
     tx: Sender<Action>,
     pub update: Option<UpdateCallback>,
     pub cred: Arc<NetCredential>,
+    pub profile: Option<String>,
     pub http: HttpClient,
     pub state: NetState,
+    pub ip: Option<IpAddr>,
+    pub portal: Option<Arc<PortalConfig>>,
     pub status: NetStatus,
+    network: Option<String>,
     pub accent: ThemeColor,
     pub log: Cow<'static, str>,
     log_busy: BusyBool,
     online_busy: BusyBool,
     detail_busy: BusyBool,
     pub flux: NetFlux,
+    alert_flux: NetFlux,
+    pub thresholds: Thresholds,
+    pub alerts: Vec<Alert>,
+    pub notify: bool,
+    pub hooks: Vec<Hook>,
+    pub login_success: u64,
+    pub login_failure: u64,
+    pub refresh: Option<u64>,
+    pub retry: RetryPolicy,
+    ticks: u64,
+    status_ticks: u64,
+    logout_pending: bool,
     pub users: Vec<NetUser>,
     pub details: Vec<NetDetail>,
+    pub tariff: Tariff,
     pub mac_addrs: Vec<MacAddress>,
     pub del_at_exit: AtomicBool,
 }
@@ -40,6 +70,9 @@ impl Model {
     pub fn new(tx: Sender<Action>) -> Result<Self> {
         let http = create_http_client()?;
 
+        let status = NetStatus::current();
+        let network = status.network();
+
         let mac_addrs = MacAddressIterator::new()
             .map(|it| it.collect::<Vec<_>>())
             .unwrap_or_default();
@@ -48,30 +81,75 @@ impl Model {
             update: None,
             tx: tx.clone(),
             cred: Arc::new(NetCredential::default()),
+            profile: FileSettingsReader::default_profile(),
             http,
             state: NetState::Unknown,
-            status: NetStatus::current(),
+            ip: None,
+            portal: None,
+            status,
+            network,
             accent: ThemeColor::accent(),
             log: Cow::default(),
             log_busy: BusyBool::new(tx.clone(), UpdateMsg::LogBusy),
             online_busy: BusyBool::new(tx.clone(), UpdateMsg::OnlineBusy),
             detail_busy: BusyBool::new(tx, UpdateMsg::DetailBusy),
             flux: NetFlux::default(),
+            alert_flux: NetFlux::default(),
+            thresholds: Thresholds::default(),
+            alerts: Vec::default(),
+            notify: false,
+            hooks: Vec::default(),
+            login_success: 0,
+            login_failure: 0,
+            refresh: None,
+            retry: RetryPolicy::default(),
+            ticks: 0,
+            status_ticks: 0,
+            logout_pending: false,
             users: Vec::default(),
             details: Vec::default(),
+            tariff: Tariff::default(),
             mac_addrs,
             del_at_exit: AtomicBool::new(false),
         })
     }
 
+    pub fn load_preferences(&mut self, prefs: &Preferences) -> Result<()> {
+        self.http = prefs.http.build()?;
+        self.portal = prefs.portal()?.map(Arc::new);
+        self.tariff = prefs.tariff()?;
+        if !prefs.alerts.is_empty() {
+            self.thresholds.flux = prefs.flux_alerts()?;
+        }
+        if let Some(b) = prefs.balance_alert {
+            self.thresholds.balance = Some(Balance(b));
+        }
+        self.hooks = prefs
+            .hooks
+            .iter()
+            .map(|h| h.parse())
+            .collect::<Result<_>>()?;
+        self.refresh = prefs.refresh.filter(|r| *r > 0);
+        self.retry = prefs.retry.clone().unwrap_or_default();
+        Ok(())
+    }
+
     pub fn queue(&self, action: Action) {
         let tx = self.tx.clone();
         tokio::spawn(async move { tx.send(action).await.ok() });
     }
 
     pub fn handle(&mut self, action: Action) {
+        match &action {
+            // 设置中可能有代理地址与钩子的令牌。
+            Action::Preferences(_) => tracing::trace!(action = "Preferences", "处理消息"),
+            _ => tracing::trace!(?action, "处理消息"),
+        }
         match action {
             Action::Credential(cred) => {
+                if cred.username != self.cred.username {
+                    self.alert_flux = NetFlux::default();
+                }
                 self.cred = cred;
                 self.update(UpdateMsg::Credential);
             }
@@ -87,23 +165,72 @@ impl Model {
                     .ok()
                 });
             }
+            Action::Profile(p) => {
+                let old = std::mem::replace(&mut self.profile, p.clone());
+                // 旧配置的数据不属于新配置的用户。
+                self.flux = NetFlux::default();
+                self.alert_flux = NetFlux::default();
+                self.users.clear();
+                self.details.clear();
+                self.update(UpdateMsg::Flux);
+                self.update(UpdateMsg::Online);
+                self.update(UpdateMsg::Details);
+                let cred = self.cred.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    if !cred.username.is_empty() {
+                        FileSettingsReader::with_profile(old.as_deref())?
+                            .save(cred)
+                            .await?;
+                    }
+                    let reader = FileSettingsReader::with_profile(p.as_deref())?;
+                    let cred = reader.read_with_password().unwrap_or_default();
+                    tx.send(Action::Preferences(Box::new(reader.read_preferences())))
+                        .await?;
+                    tx.send(Action::Credential(Arc::new(cred))).await?;
+                    tx.send(Action::State(reader.read_host())).await?;
+                    Ok::<_, anyhow::Error>(())
+                });
+                tracing::info!(profile = ?self.profile, "切换配置");
+                self.log =
+                    format!("已切换到配置 {}", self.profile.as_deref().unwrap_or("默认")).into();
+                self.update(UpdateMsg::Log);
+                self.update(UpdateMsg::Profile);
+            }
+            Action::Preferences(prefs) => {
+                if let Err(e) = self.load_preferences(&prefs) {
+                    tracing::warn!("无法加载设置：{}", e);
+                    self.log = e.to_string().into();
+                    self.update(UpdateMsg::Log);
+                }
+            }
             Action::State(s) => {
                 match s {
                     None => {
                         let tx = self.tx.clone();
                         let http = self.http.clone();
-                        let status = self.status.clone();
                         tokio::spawn(async move {
+                            let status = tokio::task::spawn_blocking(NetStatus::current)
+                                .await
+                                .unwrap_or(NetStatus::Unknown);
+                            let network = status.network();
+                            tx.send(Action::Status(status.clone(), network)).await.ok();
                             let state = suggest::suggest_with_status(&http, status).await;
                             tx.send(Action::State(Some(state))).await.ok()
                         });
                     }
                     Some(s) => {
+                        if self.state != s {
+                            tracing::info!(from = ?self.state, to = ?s, "连接方式改变");
+                        }
                         self.state = s;
                         self.update(UpdateMsg::State);
                     }
                 };
             }
+            Action::Status(status, network) => {
+                self.set_status(status, network);
+            }
             Action::Timer => {
                 self.spawn_timer();
             }
@@ -113,63 +240,95 @@ impl Model {
                         Duration(self.flux.online_time.0 + NaiveDuration::seconds(1));
                     self.update(UpdateMsg::Flux);
                 }
+                self.status_ticks += 1;
+                if self.status_ticks >= STATUS_INTERVAL {
+                    self.status_ticks = 0;
+                    self.spawn_status();
+                }
+                if let Some(refresh) = self.refresh {
+                    self.ticks += 1;
+                    if self.ticks >= refresh {
+                        self.ticks = 0;
+                        self.spawn_flux();
+                    }
+                }
             }
             Action::Login => {
-                self.log = "正在登录".into();
-                self.update(UpdateMsg::Log);
-                self.spawn_login();
+                self.login();
             }
             Action::Logout => {
-                self.log = "正在注销".into();
-                self.update(UpdateMsg::Log);
-                self.spawn_logout();
+                self.logout();
             }
             Action::Flux => {
                 self.spawn_flux();
             }
-            Action::LoginDone(s) | Action::LogoutDone(s) => {
+            Action::LoginDone(res) => {
+                let (event, s) = match res {
+                    Ok(s) => {
+                        tracing::info!("登录成功：{}", s);
+                        self.login_success += 1;
+                        (HookEvent::LoginSucceeded, s)
+                    }
+                    Err(s) => {
+                        tracing::warn!("登录失败：{}", s);
+                        self.login_failure += 1;
+                        (HookEvent::LoginFailed, s)
+                    }
+                };
+                self.fire(event, s.clone());
                 self.log = s.into();
                 self.update(UpdateMsg::Log);
             }
-            Action::FluxDone(f, s, keep) => {
-                if keep {
-                    if let Some(s) = s {
+            Action::LogoutDone(res) => {
+                match &res {
+                    Ok(s) => tracing::info!("注销成功：{}", s),
+                    Err(s) => tracing::warn!("注销失败：{}", s),
+                }
+                self.logout_pending = res.is_ok();
+                self.log = res.unwrap_or_else(|e| e).into();
+                self.update(UpdateMsg::Log);
+            }
+            Action::FluxDone(res, keep) => {
+                let f = match res {
+                    Ok(f) => f,
+                    // 查询失败不代表已经断开，保留之前的流量。
+                    Err(s) => {
                         self.log = s.into();
                         self.update(UpdateMsg::Log);
+                        return;
                     }
-                } else {
-                    self.log = s.unwrap_or_default().into();
+                };
+                if !self.flux.username.is_empty() && f.username.is_empty() && !self.logout_pending {
+                    tracing::warn!(username = %self.flux.username, "连接已断开");
+                    self.fire(HookEvent::SessionDropped, String::new());
+                }
+                self.logout_pending = false;
+                if !keep {
+                    self.log = Cow::default();
                     self.update(UpdateMsg::Log);
                 }
+                let alerts = self.thresholds.crossed(&self.alert_flux, &f);
+                if !f.username.is_empty() {
+                    self.alert_flux = f.clone();
+                }
                 self.flux = f;
                 self.update(UpdateMsg::Flux);
+                if !alerts.is_empty() {
+                    self.alert(alerts);
+                }
             }
             Action::Online => {
-                self.spawn_online();
+                self.spawn_online(UserOp::List);
             }
             Action::OnlineDone(us) => {
                 self.users = us;
                 self.update(UpdateMsg::Online);
             }
             Action::Connect(addr) => {
-                let tx = self.tx.clone();
-                let usereg = self.usereg();
-                tokio::spawn(async move {
-                    usereg.login().await?;
-                    usereg.connect(addr).await?;
-                    tx.send(Action::Online).await?;
-                    Ok::<_, anyhow::Error>(())
-                });
+                self.spawn_online(UserOp::Connect(addr));
             }
             Action::Drop(addr) => {
-                let tx = self.tx.clone();
-                let usereg = self.usereg();
-                tokio::spawn(async move {
-                    usereg.login().await?;
-                    usereg.drop(addr).await?;
-                    tx.send(Action::Online).await?;
-                    Ok::<_, anyhow::Error>(())
-                });
+                self.spawn_online(UserOp::Drop(addr));
             }
             Action::Details => {
                 self.spawn_details();
@@ -191,6 +350,80 @@ impl Model {
         }
     }
 
+    fn set_status(&mut self, status: NetStatus, network: Option<String>) {
+        let changed = self.network != network;
+        self.status = status;
+        if changed {
+            tracing::info!(from = ?self.network, to = ?network, "网络改变");
+            self.network = network;
+            self.fire(HookEvent::NetworkChanged, self.status.to_string());
+        }
+    }
+
+    fn spawn_status(&self) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let (status, network) = tokio::task::spawn_blocking(|| {
+                let status = NetStatus::current();
+                let network = status.network();
+                (status, network)
+            })
+            .await?;
+            tx.send(Action::Status(status, network)).await?;
+            Ok::<_, anyhow::Error>(())
+        });
+    }
+
+    fn fire(&self, event: HookEvent, message: String) {
+        let hooks = self
+            .hooks
+            .iter()
+            .filter(|h| h.event == event)
+            .cloned()
+            .collect::<Vec<_>>();
+        if hooks.is_empty() {
+            return;
+        }
+        tracing::debug!(event = event.name(), count = hooks.len(), "触发钩子");
+        let payload = json!({
+            "event": event.name(),
+            "message": message,
+            "username": self.cred.username,
+            "flux": self.flux.flux.0,
+            "balance": self.flux.balance.0,
+            "state": self.state.to_string(),
+            "network": self.status.to_string(),
+        });
+        for hook in hooks {
+            let payload = payload.clone();
+            let http = self.http.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HOOK_TIMEOUT, hook.run(&payload, &http)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => tracing::warn!(event = event.name(), "钩子运行失败：{}", e),
+                    Err(_) => tracing::warn!(event = event.name(), "钩子运行超时"),
+                }
+            });
+        }
+    }
+
+    fn alert(&mut self, alerts: Vec<Alert>) {
+        self.fire(
+            HookEvent::ThresholdCrossed,
+            alerts
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+        if self.notify {
+            let alerts = alerts.clone();
+            tokio::task::spawn_blocking(move || alert::notify(&alerts).ok());
+        }
+        self.alerts = alerts;
+        self.update(UpdateMsg::Alert);
+    }
+
     fn spawn_timer(&self) {
         let tx = self.tx.clone();
         tokio::spawn(async move {
@@ -204,114 +437,194 @@ impl Model {
         });
     }
 
-    fn client(&self) -> Option<TUNetConnect> {
-        TUNetConnect::new(self.state, self.cred.clone(), self.http.clone()).ok()
+    fn client(&self) -> Result<TUNetConnect> {
+        TUNetConnect::new(self.state, self.cred.clone(), self.http.clone()).and_then(|c| {
+            c.with_portal(self.portal.clone())
+                .with_retry(self.retry.clone())
+                .with_ip(self.ip)
+        })
     }
 
     fn usereg(&self) -> UseregHelper {
-        UseregHelper::new(self.cred.clone(), self.http.clone())
+        UseregHelper::new(self.cred.clone(), self.http.clone()).with_retry(self.retry.clone())
     }
 
-    fn spawn_login(&self) {
-        if let Some(lock) = self.log_busy.lock() {
-            let tx = self.tx.clone();
-            if let Some(client) = self.client() {
-                tokio::spawn(async move {
-                    let _lock = lock;
-                    let res = client.login().await;
-                    let ok = res.is_ok();
-                    tx.send(Action::LoginDone(res.unwrap_or_else(|e| e.to_string())))
-                        .await?;
-                    if ok {
-                        Self::flux_impl(client, tx, true).await?;
-                    }
-                    Ok::<_, anyhow::Error>(())
-                });
+    /// 开始登录。已有操作在进行时返回 `None`。
+    pub fn login(&mut self) -> Option<Task> {
+        self.log = "正在登录".into();
+        self.update(UpdateMsg::Log);
+        self.spawn_login()
+    }
+
+    /// 开始注销。已有操作在进行时返回 `None`。
+    pub fn logout(&mut self) -> Option<Task> {
+        self.log = "正在注销".into();
+        self.update(UpdateMsg::Log);
+        self.spawn_logout()
+    }
+
+    fn spawn_login(&self) -> Option<Task> {
+        let lock = self.log_busy.lock()?;
+        let tx = self.tx.clone();
+        let client = self.client();
+        let span = tracing::info_span!("login", state = ?self.state);
+        Some(tokio::spawn(
+            async move {
+                let _lock = lock;
+                let res = match client {
+                    Ok(client) => client.login().await.map(|s| (s, client)),
+                    Err(e) => Err(e),
+                };
+                tx.send(Action::LoginDone(
+                    res.as_ref()
+                        .map(|(s, _)| s.clone())
+                        .map_err(|e| e.to_string()),
+                ))
+                .await?;
+                let (_, client) = res?;
+                Self::flux_impl(client, tx, true).await.ok();
+                Ok(())
             }
-        }
+            .instrument(span),
+        ))
     }
 
-    fn spawn_logout(&self) {
-        if let Some(lock) = self.log_busy.lock() {
-            let tx = self.tx.clone();
-            if let Some(client) = self.client() {
-                tokio::spawn(async move {
-                    let _lock = lock;
-                    let res = client.logout().await;
-                    let ok = res.is_ok();
-                    tx.send(Action::LoginDone(res.unwrap_or_else(|e| e.to_string())))
-                        .await?;
-                    if ok {
-                        Self::flux_impl(client, tx, true).await?;
-                    }
-                    Ok::<_, anyhow::Error>(())
-                });
+    fn spawn_logout(&self) -> Option<Task> {
+        let lock = self.log_busy.lock()?;
+        let tx = self.tx.clone();
+        let client = self.client();
+        let span = tracing::info_span!("logout", state = ?self.state);
+        Some(tokio::spawn(
+            async move {
+                let _lock = lock;
+                let res = match client {
+                    Ok(client) => client.logout().await.map(|s| (s, client)),
+                    Err(e) => Err(e),
+                };
+                tx.send(Action::LogoutDone(
+                    res.as_ref()
+                        .map(|(s, _)| s.clone())
+                        .map_err(|e| e.to_string()),
+                ))
+                .await?;
+                let (_, client) = res?;
+                Self::flux_impl(client, tx, true).await.ok();
+                Ok(())
             }
-        }
+            .instrument(span),
+        ))
     }
 
-    fn spawn_flux(&self) {
-        if let Some(lock) = self.log_busy.lock() {
-            let tx = self.tx.clone();
-            if let Some(client) = self.client() {
-                tokio::spawn(async move {
-                    let _lock = lock;
-                    Self::flux_impl(client, tx, false).await
-                });
+    fn spawn_flux(&self) -> Option<Task> {
+        let lock = self.log_busy.lock()?;
+        let tx = self.tx.clone();
+        let client = self.client();
+        let span = tracing::info_span!("flux", state = ?self.state);
+        Some(tokio::spawn(
+            async move {
+                let _lock = lock;
+                match client {
+                    Ok(client) => Self::flux_impl(client, tx, false).await,
+                    Err(e) => {
+                        tx.send(Action::FluxDone(Err(e.to_string()), false)).await?;
+                        Err(e)
+                    }
+                }
             }
-        }
+            .instrument(span),
+        ))
     }
 
     async fn flux_impl(client: TUNetConnect, tx: Sender<Action>, keep_msg: bool) -> Result<()> {
         let flux = client.flux().await;
         match flux {
             Ok(flux) => {
-                tx.send(Action::FluxDone(flux, None, keep_msg)).await?;
+                tx.send(Action::FluxDone(Ok(flux), keep_msg)).await?;
+                Ok(())
             }
             Err(err) => {
-                tx.send(Action::FluxDone(
-                    NetFlux::default(),
-                    Some(err.to_string()),
-                    keep_msg,
-                ))
-                .await?
+                tx.send(Action::FluxDone(Err(err.to_string()), keep_msg))
+                    .await?;
+                Err(err)
             }
         }
-        Ok(())
     }
 
-    fn spawn_online(&self) {
-        if let Some(lock) = self.online_busy.lock() {
-            let tx = self.tx.clone();
-            let usereg = self.usereg();
-            tokio::spawn(async move {
+    fn spawn_online(&self, op: UserOp) -> Option<Task> {
+        let lock = self.online_busy.lock()?;
+        let tx = self.tx.clone();
+        let usereg = self.usereg();
+        Some(tokio::spawn(
+            async move {
                 let _lock = lock;
                 usereg.login().await?;
+                match op {
+                    UserOp::List => {}
+                    UserOp::Connect(addr) => {
+                        usereg.connect(addr).await?;
+                    }
+                    UserOp::Drop(addr) => {
+                        usereg.drop(addr).await?;
+                    }
+                }
                 let users = usereg.users();
                 pin_mut!(users);
                 tx.send(Action::OnlineDone(users.try_collect().await?))
                     .await?;
-                Ok::<_, anyhow::Error>(())
-            });
-        }
+                Ok(())
+            }
+            .instrument(tracing::info_span!("online", ?op)),
+        ))
+    }
+
+    /// 刷新流量。
+    pub fn flux(&self) -> Option<Task> {
+        self.spawn_flux()
+    }
+
+    /// 刷新在线设备列表。
+    pub fn online(&self) -> Option<Task> {
+        self.spawn_online(UserOp::List)
+    }
+
+    /// 认证并刷新在线设备列表。
+    pub fn connect(&self, addr: Ipv4Addr) -> Option<Task> {
+        self.spawn_online(UserOp::Connect(addr))
+    }
+
+    /// 下线并刷新在线设备列表。
+    pub fn drop(&self, addr: Ipv4Addr) -> Option<Task> {
+        self.spawn_online(UserOp::Drop(addr))
     }
 
     fn spawn_details(&self) {
         if let Some(lock) = self.detail_busy.lock() {
             let tx = self.tx.clone();
             let usereg = self.usereg();
-            tokio::spawn(async move {
-                let _lock = lock;
-                usereg.login().await?;
-                let details = usereg.details(NetDetailOrder::LogoutTime, false);
-                pin_mut!(details);
-                tx.send(Action::DetailsDone(details.try_collect().await?))
-                    .await?;
-                Ok::<_, anyhow::Error>(())
-            });
+            tokio::spawn(
+                async move {
+                    let _lock = lock;
+                    usereg.login().await?;
+                    let details = usereg.details(NetDetailOrder::LogoutTime, false);
+                    pin_mut!(details);
+                    tx.send(Action::DetailsDone(details.try_collect().await?))
+                        .await?;
+                    Ok::<_, anyhow::Error>(())
+                }
+                .instrument(tracing::info_span!("details")),
+            );
         }
     }
 
+    pub fn estimate(&self) -> Estimate {
+        self.tariff.estimate(
+            self.flux.flux,
+            &self.details,
+            ProjectionMethod::AVERAGE,
+            campus_now(),
+        )
+    }
+
     pub fn log_busy(&self) -> bool {
         self.log_busy.get()
     }
@@ -333,19 +646,32 @@ impl Model {
     }
 }
 
+/// 后台操作，结束时给出操作是否成功。
+pub type Task = tokio::task::JoinHandle<Result<()>>;
+
+#[derive(Debug, Clone, Copy)]
+enum UserOp {
+    List,
+    Connect(Ipv4Addr),
+    Drop(Ipv4Addr),
+}
+
 #[derive(Debug)]
 pub enum Action {
     Credential(Arc<NetCredential>),
-    UpdateCredential(String, String),
+    UpdateCredential(String, Secret),
+    Profile(Option<String>),
+    Preferences(Box<Preferences>),
     State(Option<NetState>),
+    Status(NetStatus, Option<String>),
     Timer,
     Tick,
     Login,
-    LoginDone(String),
+    LoginDone(std::result::Result<String, String>),
     Logout,
-    LogoutDone(String),
+    LogoutDone(std::result::Result<String, String>),
     Flux,
-    FluxDone(NetFlux, Option<String>, bool),
+    FluxDone(std::result::Result<NetFlux, String>, bool),
     Online,
     OnlineDone(Vec<NetUser>),
     Connect(Ipv4Addr),
@@ -367,6 +693,8 @@ pub enum UpdateMsg {
     LogBusy,
     OnlineBusy,
     DetailBusy,
+    Alert,
+    Profile,
 }
 
 struct BusyBool {
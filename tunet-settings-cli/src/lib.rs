@@ -1,8 +1,30 @@
 use rpassword::read_password;
-use std::io::{stdin, stdout, Write};
-use std::sync::Arc;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::{stderr, stdin, stdout, IsTerminal, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::{fmt, prelude::*};
 use tunet_helper::*;
-use tunet_settings::*;
+
+pub use tunet_settings::{write_token, FileSettingsReader, Preferences, CONFIG_ENV, PROFILE_ENV};
+
+pub static USERNAME_ENV: &str = "TUNET_USERNAME";
+pub static PASSWORD_ENV: &str = "TUNET_PASSWORD";
+
+static STDIN_PASSWORD: Mutex<Option<Secret>> = Mutex::new(None);
+static HTTP_OPTIONS: Mutex<Option<HttpClientOptions>> = Mutex::new(None);
+#[cfg(feature = "fixtures")]
+static FIXTURES: Mutex<Option<Arc<Fixtures>>> = Mutex::new(None);
+
+static LOG_TARGETS: &[&str] = &[
+    "tunet",
+    "tunet_cui",
+    "tunet_helper",
+    "tunet_suggest",
+    "tunet_settings",
+    "tunet_model",
+];
 
 struct StdioSettingsReader;
 
@@ -15,40 +37,125 @@ impl StdioSettingsReader {
         Ok(u.replace(&['\n', '\r'][..], ""))
     }
 
-    fn read_password(&self) -> Result<String> {
+    fn read_password(&self) -> Result<Secret> {
         print!("请输入密码：");
         stdout().flush()?;
-        Ok(read_password()?)
+        Ok(read_password()?.into())
     }
+}
 
-    pub fn read(&self) -> Result<NetCredential> {
-        let u = self.read_username()?;
-        Ok(NetCredential::new(u, String::new(), Vec::new()))
-    }
+fn env_var(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|s| !s.is_empty())
+}
+
+/// 从标准输入读取一行作为密码，由之后第一次 [`read_cred`] 取走。
+pub fn read_password_stdin() -> Result<()> {
+    let mut p = String::new();
+    stdin().read_line(&mut p)?;
+    let len = p.trim_end_matches(&['\n', '\r'][..]).len();
+    p.truncate(len);
+    *STDIN_PASSWORD.lock().unwrap() = Some(p.into());
+    Ok(())
+}
 
-    pub fn read_with_password(&self) -> Result<NetCredential> {
-        let u = self.read_username()?;
-        let p = self.read_password()?;
-        Ok(NetCredential::new(u, p, Vec::new()))
+fn read_saved(with_password: bool) -> NetCredential {
+    let saved = FileSettingsReader::new().and_then(|reader| {
+        if with_password {
+            reader.read_with_password().or_else(|_| reader.read())
+        } else {
+            reader.read()
+        }
+    });
+    let mut cred = saved.unwrap_or_default();
+    if let Some(u) = env_var(USERNAME_ENV) {
+        if u != cred.username {
+            cred = NetCredential::new(u, String::new(), AcIdCache::default());
+        }
     }
+    cred
 }
 
+/// 读取用户名和密码。
+///
+/// 用户名依次来自：环境变量 `TUNET_USERNAME`、设置文件、终端输入。
+/// 密码依次来自：`--password-stdin`、环境变量 `TUNET_PASSWORD`、
+/// 密码存储、设置中的 `password_command`、终端输入。
 pub fn read_cred() -> Result<Arc<NetCredential>> {
-    if let Ok(reader) = FileSettingsReader::new() {
-        if let Ok(cred) = reader.read_with_password() {
-            return Ok(Arc::new(cred));
-        }
+    let password = STDIN_PASSWORD
+        .lock()
+        .unwrap()
+        .take()
+        .or_else(|| env_var(PASSWORD_ENV).map(Secret::from));
+    let mut cred = read_saved(password.is_none());
+    if cred.username.is_empty() {
+        cred.username = StdioSettingsReader.read_username()?;
+    }
+    if let Some(p) = password {
+        cred.password = p;
+    }
+    if cred.password.is_empty() {
+        cred.password = StdioSettingsReader.read_password()?;
     }
-    Ok(Arc::new(StdioSettingsReader.read_with_password()?))
+    Ok(Arc::new(cred))
 }
 
+/// 读取用户名，来源顺序与 [`read_cred`] 相同。
 pub fn read_username() -> Result<Arc<NetCredential>> {
-    if let Ok(reader) = FileSettingsReader::new() {
-        if let Ok(cred) = reader.read() {
-            return Ok(Arc::new(cred));
-        }
+    let mut cred = read_saved(false);
+    if cred.username.is_empty() {
+        cred.username = StdioSettingsReader.read_username()?;
     }
-    Ok(Arc::new(StdioSettingsReader.read()?))
+    Ok(Arc::new(cred))
+}
+
+pub fn read_host() -> Option<NetState> {
+    FileSettingsReader::new().ok()?.read_host()
+}
+
+pub fn read_portal() -> Result<Option<Arc<PortalConfig>>> {
+    Ok(read_preferences().portal()?.map(Arc::new))
+}
+
+/// 命令行中指定的 HTTP 客户端选项，覆盖设置文件中的值。
+pub fn set_http_options(options: HttpClientOptions) {
+    *HTTP_OPTIONS.lock().unwrap() = Some(options);
+}
+
+pub fn http_client() -> Result<HttpClient> {
+    HTTP_OPTIONS
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_default()
+        .or(read_preferences().http)
+        .build()
+}
+
+/// 命令行中指定的记录或回放夹具。
+#[cfg(feature = "fixtures")]
+pub fn set_fixtures(fixtures: Option<Fixtures>) {
+    *FIXTURES.lock().unwrap() = fixtures.map(Arc::new);
+}
+
+#[cfg(feature = "fixtures")]
+pub fn read_fixtures() -> Option<Arc<Fixtures>> {
+    FIXTURES.lock().unwrap().clone()
+}
+
+/// 未启用 `fixtures` 特性时总是直接访问网络。
+#[cfg(not(feature = "fixtures"))]
+pub fn read_fixtures() -> Option<Arc<Fixtures>> {
+    None
+}
+
+pub fn read_retry() -> RetryPolicy {
+    read_preferences().retry.unwrap_or_default()
+}
+
+pub fn read_preferences() -> Preferences {
+    FileSettingsReader::new()
+        .map(|reader| reader.read_preferences())
+        .unwrap_or_default()
 }
 
 pub async fn save_cred(cred: Arc<NetCredential>) -> Result<()> {
@@ -67,3 +174,41 @@ pub fn delete_cred() -> Result<()> {
     }
     Ok(())
 }
+
+/// 初始化日志。`verbose` 为 `-v` 的个数，依次输出信息、调试与全部日志，
+/// 其他库只输出警告。指定 `log_file` 时追加写入该文件，否则输出到标准错误。
+pub fn init_logging(verbose: u8, log_file: Option<&Path>) -> Result<()> {
+    let level = match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let filter = Targets::new()
+        .with_targets(LOG_TARGETS.iter().map(|t| (*t, level)))
+        .with_default(LevelFilter::WARN);
+    let (file, stderr) = match log_file {
+        Some(path) => {
+            if let Some(p) = path.parent() {
+                DirBuilder::new().recursive(true).create(p)?;
+            }
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            let layer = fmt::layer().with_ansi(false).with_writer(Mutex::new(file));
+            (Some(layer), None)
+        }
+        None => {
+            let layer = fmt::layer()
+                .without_time()
+                .with_target(verbose > 0)
+                .with_ansi(stderr().is_terminal())
+                .with_writer(stderr);
+            (None, Some(layer))
+        }
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(file)
+        .with(stderr)
+        .try_init()?;
+    Ok(())
+}
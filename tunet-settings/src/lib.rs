@@ -1,119 +1,477 @@
 #![forbid(unsafe_code)]
 
 use dirs::config_dir;
-use keyring::Keyring;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
 use std::borrow::Cow;
 use std::fs::{remove_file, DirBuilder, File};
 use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
 use tunet_helper::*;
 
+mod prefs;
+mod store;
+
+pub use prefs::*;
+pub use store::*;
+
 #[derive(Deserialize, Serialize)]
 struct Settings<'a> {
     #[serde(default)]
-    pub username: Cow<'a, str>,
+    pub version: u64,
     #[serde(default)]
+    pub username: Cow<'a, str>,
+    #[serde(default, skip_serializing_if = "is_empty")]
     pub password: Cow<'a, str>,
     #[serde(default)]
-    pub ac_ids: Cow<'a, [i32]>,
+    pub ac_ids: AcIdCache,
+    #[serde(flatten)]
+    pub prefs: Preferences,
+}
+
+fn is_empty<T: AsRef<str>>(s: &T) -> bool {
+    s.as_ref().is_empty()
 }
 
 impl From<Settings<'_>> for NetCredential {
     fn from(s: Settings) -> Self {
-        Self::new(
-            s.username.into_owned(),
-            s.password.into_owned(),
-            s.ac_ids.into_owned(),
-        )
+        Self::new(s.username.into_owned(), s.password.into_owned(), s.ac_ids)
     }
 }
 
 static TUNET_NAME: &str = "tunet";
 
+pub static PROFILE_ENV: &str = "TUNET_PROFILE";
+pub static CONFIG_ENV: &str = "TUNET_CONFIG";
+
+static DEFAULT_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+static DEFAULT_CONFIG: Mutex<Option<PathBuf>> = Mutex::new(None);
+
 pub struct FileSettingsReader {
     path: PathBuf,
-    keyring: Keyring,
+    key: String,
+    kind: Option<StoreKind>,
+    store: Box<dyn CredentialStore>,
 }
 
 impl FileSettingsReader {
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            path: Self::file_path()?,
-            keyring: Keyring::new(TUNET_NAME)?,
+        Self::with_profile(Self::default_profile().as_deref())
+    }
+
+    pub fn with_profile(profile: Option<&str>) -> Result<Self> {
+        let path = Self::profile_path(profile)?;
+        let key = Self::store_key(profile, &path)?;
+        let kind = Self::read_settings(&path).and_then(|s| s.prefs.store);
+        let store = Self::create_store(&path, &key, kind.unwrap_or_default())?;
+        Ok(Self::with_store(path, key, kind, store))
+    }
+
+    /// 密码存储中的键名。通过 `--config` 或 `TUNET_CONFIG` 指定的设置文件按其路径区分，
+    /// 不会与默认设置共用同一个密码。
+    fn store_key(profile: Option<&str>, path: &Path) -> Result<String> {
+        if let Some(profile) = profile {
+            return Ok(format!("{}-{}", TUNET_NAME, profile));
+        }
+        let default = Self::config_dir()?.join("settings.json");
+        if path == default {
+            return Ok(TUNET_NAME.to_string());
+        }
+        let path = canonical_path(path)?;
+        // FNV-1a，结果在不同版本间保持不变。
+        let hash = path
+            .to_string_lossy()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |h, b| {
+                (h ^ b as u64).wrapping_mul(0x100000001b3)
+            });
+        // 配置名称中不能有 `@`，因此不会与配置的键名冲突。
+        Ok(format!("{}@{:016x}", TUNET_NAME, hash))
+    }
+
+    pub fn with_store(
+        path: PathBuf,
+        key: String,
+        kind: Option<StoreKind>,
+        store: Box<dyn CredentialStore>,
+    ) -> Self {
+        Self {
+            path,
+            key,
+            kind,
+            store,
+        }
+    }
+
+    fn create_store(path: &Path, key: &str, kind: StoreKind) -> Result<Box<dyn CredentialStore>> {
+        Ok(match kind {
+            StoreKind::Keyring => Box::new(KeyringStore::new(key)?),
+            StoreKind::File => Box::new(FileStore::new(
+                path.with_extension("password"),
+                Self::config_dir()?.join("secret.key"),
+                std::env::var(PASSPHRASE_ENV).ok(),
+            )),
+            StoreKind::Memory => Box::new(MemoryStore::default()),
         })
     }
 
-    pub fn file_path() -> Result<PathBuf> {
+    fn read_value(path: &Path) -> Result<JsonValue> {
+        let value = match File::open(path) {
+            Ok(f) => serde_json::from_reader(BufReader::new(f))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => JsonValue::Object(JsonMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+        migrate(value)
+    }
+
+    fn read_settings(path: &Path) -> Option<Settings<'static>> {
+        Self::read_value(path)
+            .ok()
+            .and_then(|v| serde_json::from_value(v).ok())
+    }
+
+    fn config_dir() -> Result<PathBuf> {
         let mut p = config_dir().ok_or_else(|| anyhow::anyhow!("找不到配置文件目录"))?;
         p.push(TUNET_NAME);
-        p.push("settings");
+        Ok(p)
+    }
+
+    /// 配置文件目录中的文件，与配置无关。
+    pub fn data_path(name: &str) -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join(name))
+    }
+
+    /// 命令行中指定的配置名称或设置文件路径，优先于环境变量，
+    /// 且不会传给钩子等子进程。
+    pub fn set_default(profile: Option<String>, config: Option<PathBuf>) {
+        *DEFAULT_PROFILE.lock().unwrap() = profile;
+        *DEFAULT_CONFIG.lock().unwrap() = config;
+    }
+
+    pub fn default_profile() -> Option<String> {
+        if DEFAULT_CONFIG.lock().unwrap().is_some() {
+            return None;
+        }
+        DEFAULT_PROFILE
+            .lock()
+            .unwrap()
+            .clone()
+            .or_else(|| std::env::var(PROFILE_ENV).ok())
+            .filter(|p| !p.is_empty())
+    }
+
+    /// 设置文件路径。未指定配置名称时，`--config` 或 `TUNET_CONFIG` 优先。
+    pub fn profile_path(profile: Option<&str>) -> Result<PathBuf> {
+        if profile.is_none() {
+            let config = DEFAULT_CONFIG.lock().unwrap().clone();
+            if let Some(p) = config.or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from)) {
+                if !p.as_os_str().is_empty() {
+                    return Ok(p);
+                }
+            }
+        }
+        let mut p = Self::config_dir()?;
+        match profile {
+            Some(profile) => {
+                if profile.is_empty()
+                    || !profile
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+                {
+                    return Err(anyhow::anyhow!("无效的配置名称：{}", profile));
+                }
+                p.push("profiles");
+                p.push(profile);
+            }
+            None => p.push("settings"),
+        }
         p.set_extension("json");
         Ok(p)
     }
 
+    pub fn profiles() -> Result<Vec<String>> {
+        let mut p = Self::config_dir()?;
+        p.push("profiles");
+        let mut profiles = match std::fs::read_dir(p) {
+            Ok(dir) => dir
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map(|e| e == "json").unwrap_or(false))
+                .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+                .collect::<Vec<_>>(),
+            Err(_) => Vec::new(),
+        };
+        profiles.sort();
+        Ok(profiles)
+    }
+
+    pub fn file_path() -> Result<PathBuf> {
+        Self::profile_path(Self::default_profile().as_deref())
+    }
+
     pub fn file_exists() -> bool {
         Self::file_path().map(|p| p.exists()).unwrap_or(false)
     }
 
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[tracing::instrument(skip_all, fields(path = %self.path.display()))]
     pub async fn save(&mut self, settings: Arc<NetCredential>) -> Result<()> {
+        if let Some(p) = self.path.parent() {
+            DirBuilder::new().recursive(true).create(p)?;
+        }
+        if let Err(e) = self.store.set(settings.password.expose()) {
+            if self.kind.is_some() {
+                return Err(e);
+            }
+            tracing::warn!("无法保存密码，改用文件存储：{}", e);
+            self.kind = Some(StoreKind::File);
+            self.store = Self::create_store(&self.path, &self.key, StoreKind::File)?;
+            self.store.set(settings.password.expose())?;
+        }
+        let mut prefs = Self::read_settings(&self.path)
+            .map(|s| s.prefs)
+            .unwrap_or_default();
+        prefs.store = self.kind;
+        let ac_ids = settings.ac_ids.read().await.clone();
+        // Don't write password.
+        let c = Settings {
+            version: SETTINGS_VERSION,
+            username: Cow::Borrowed(&settings.username),
+            password: Cow::default(),
+            ac_ids,
+            prefs,
+        };
+        self.write(&c)
+    }
+
+    fn write(&self, c: &Settings) -> Result<()> {
         if let Some(p) = self.path.parent() {
             DirBuilder::new().recursive(true).create(p)?;
         }
         let f = File::create(self.path.as_path())?;
         let writer = BufWriter::new(f);
-        let ac_ids = settings.ac_ids.read().await;
-        let c = if let Err(e) = self.keyring.set(&settings.password) {
-            if cfg!(debug_assertions) {
-                eprintln!("WARNING: {}", e);
-            }
-            Settings {
-                username: Cow::Borrowed(&settings.username),
-                password: Cow::Borrowed(&settings.password),
-                ac_ids: Cow::Borrowed(ac_ids.as_ref()),
+        serde_json::to_writer_pretty(writer, c)?;
+        Ok(())
+    }
+
+    pub fn read_preferences(&self) -> Preferences {
+        Self::read_settings(&self.path)
+            .map(|s| s.prefs)
+            .unwrap_or_default()
+    }
+
+    pub fn values(&self) -> Result<JsonMap<String, JsonValue>> {
+        match Self::read_value(&self.path)? {
+            JsonValue::Object(mut map) => {
+                map.remove("password");
+                Ok(map)
             }
+            _ => Err(anyhow::anyhow!("无效的设置文件")),
+        }
+    }
+
+    pub fn get_value(&self, key: &str) -> Result<Option<JsonValue>> {
+        Ok(self.values()?.remove(key))
+    }
+
+    /// 修改一个设置项。旧版本留在设置文件中的明文密码会移入密码存储；
+    /// 修改 `store` 时把密码迁移到新的存储，并删除原有的密码。
+    pub fn set_value(&mut self, key: &str, value: JsonValue) -> Result<()> {
+        if !(key == "username" || key == "ac_ids" || Preferences::KEYS.contains(&key)) {
+            return Err(anyhow::anyhow!("不能修改设置项：{}", key));
+        }
+        let mut map = match Self::read_value(&self.path)? {
+            JsonValue::Object(map) => map,
+            _ => return Err(anyhow::anyhow!("无效的设置文件")),
+        };
+        let legacy = match map.remove("password") {
+            Some(JsonValue::String(p)) if !p.is_empty() => Some(Secret::new(p)),
+            _ => None,
+        };
+        if value.is_null() {
+            map.remove(key);
         } else {
-            // Don't write password.
-            Settings {
-                username: Cow::Borrowed(&settings.username),
-                password: Cow::default(),
-                ac_ids: Cow::Borrowed(ac_ids.as_ref()),
+            map.insert(key.to_string(), value);
+        }
+        let c: Settings = serde_json::from_value(JsonValue::Object(map))?;
+        c.prefs.validate()?;
+        let mut old = None;
+        if c.prefs.store.unwrap_or_default() != self.kind.unwrap_or_default() {
+            let mut store =
+                Self::create_store(&self.path, &self.key, c.prefs.store.unwrap_or_default())?;
+            if legacy.is_none() {
+                match self.store.get() {
+                    Ok(password) => store.set(password.expose())?,
+                    Err(e) => tracing::debug!("无法读取密码：{}", e),
+                }
             }
-        };
-        serde_json::to_writer(writer, &c)?;
+            old = Some(std::mem::replace(&mut self.store, store));
+        }
+        self.kind = c.prefs.store;
+        if let Some(password) = legacy {
+            self.store.set(password.expose())?;
+        }
+        self.write(&c)?;
+        if let Some(mut old) = old {
+            old.delete()
+                .unwrap_or_else(|e| tracing::warn!("无法删除原有的密码：{}", e));
+        }
         Ok(())
     }
 
     pub fn delete(&mut self) -> Result<()> {
-        self.keyring.delete().unwrap_or_else(|e| {
-            if cfg!(debug_assertions) {
-                eprintln!("WARNING: {}", e);
-            }
-        });
-        if Self::file_exists() {
+        self.store
+            .delete()
+            .unwrap_or_else(|e| tracing::debug!("无法删除密码：{}", e));
+        if self.path.exists() {
             remove_file(self.path.as_path())?;
         }
         Ok(())
     }
 
-    pub fn read(&self) -> Result<NetCredential> {
+    fn read_file(&self) -> Result<Settings<'static>> {
         let f = File::open(self.path.as_path())?;
-        let reader = BufReader::new(f);
-        let c: Settings = serde_json::from_reader(reader)?;
-        Ok(c.into())
+        let value = serde_json::from_reader(BufReader::new(f))?;
+        Ok(serde_json::from_value(migrate(value)?)?)
+    }
+
+    pub fn read(&self) -> Result<NetCredential> {
+        Ok(self.read_file()?.into())
+    }
+
+    pub fn read_host(&self) -> Option<NetState> {
+        self.read_preferences().host().ok().flatten()
     }
 
     pub fn read_with_password(&self) -> Result<NetCredential> {
-        let mut settings = self.read()?;
-        match self.keyring.get() {
+        let c = self.read_file()?;
+        let command = c.prefs.password_command.clone();
+        let mut settings: NetCredential = c.into();
+        match self.store.get() {
             Ok(password) => settings.password = password,
             Err(e) => {
-                if cfg!(debug_assertions) {
-                    eprintln!("WARNING: {}", e);
+                tracing::debug!("无法读取密码：{}", e);
+                if let Some(command) = command {
+                    settings.password = run_password_command(&command)?;
                 }
             }
         }
         Ok(settings)
     }
 }
+
+/// 规范化的绝对路径；文件不存在时规范化其所在目录。
+fn canonical_path(path: &Path) -> Result<PathBuf> {
+    let path = std::env::current_dir()?.join(path);
+    if let Ok(p) = path.canonicalize() {
+        return Ok(p);
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => Ok(parent
+            .canonicalize()
+            .unwrap_or_else(|_| parent.to_path_buf())
+            .join(name)),
+        _ => Ok(path),
+    }
+}
+
+fn run_password_command(command: &str) -> Result<Secret> {
+    let mut cmd = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(command);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(command);
+        c
+    };
+    let output = cmd
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("密码命令执行失败：{}", output.status));
+    }
+    // 只取第一行，原地截断以免留下明文副本。
+    let mut password = String::from_utf8(output.stdout)?;
+    let len = password.lines().next().map(str::len).unwrap_or_default();
+    password.truncate(len);
+    Ok(password.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reader(name: &str, settings: JsonValue) -> FileSettingsReader {
+        let dir =
+            std::env::temp_dir().join(format!("tunet-settings-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        DirBuilder::new().recursive(true).create(&dir).unwrap();
+        let path = dir.join("settings.json");
+        std::fs::write(&path, settings.to_string()).unwrap();
+        let kind = FileSettingsReader::read_settings(&path).and_then(|s| s.prefs.store);
+        let store = FileSettingsReader::create_store(&path, "tunet-test", kind.unwrap()).unwrap();
+        FileSettingsReader::with_store(path, "tunet-test".to_string(), kind, store)
+    }
+
+    #[test]
+    fn config_keys() {
+        let dir = std::env::temp_dir().join(format!("tunet-settings-{}-keys", std::process::id()));
+        DirBuilder::new().recursive(true).create(&dir).unwrap();
+        let key = |p: &Path| FileSettingsReader::store_key(None, p).unwrap();
+        let a = key(&dir.join("a.json"));
+        let b = key(&dir.join("b.json"));
+        assert_ne!(a, b);
+        assert_ne!(a, TUNET_NAME);
+        assert_ne!(a, FileSettingsReader::store_key(Some("a"), &dir).unwrap());
+        assert_eq!(a, key(&dir.join(".").join("a.json")));
+        std::fs::write(dir.join("a.json"), "{}").unwrap();
+        assert_eq!(a, key(&dir.join("a.json")));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let mut reader = reader("unknown", json!({ "store": "memory" }));
+        for key in ["version", "password", "refersh", "store_kind"] {
+            assert!(reader.set_value(key, json!(1)).is_err(), "{}", key);
+        }
+        reader.set_value("refresh", json!(30)).unwrap();
+        reader
+            .set_value("proxy", json!("socks5://127.0.0.1:1080"))
+            .unwrap();
+        assert_eq!(reader.get_value("refresh").unwrap(), Some(json!(30)));
+    }
+
+    #[test]
+    fn legacy_password() {
+        let mut reader = reader(
+            "legacy",
+            json!({ "version": 2, "username": "u", "password": "p", "store": "memory" }),
+        );
+        reader.set_value("refresh", json!(30)).unwrap();
+        assert_eq!(reader.store.get().unwrap().expose(), "p");
+        let text = std::fs::read_to_string(reader.path()).unwrap();
+        assert!(!text.contains("password"));
+    }
+
+    #[test]
+    fn store_migration() {
+        let mut reader = reader("migration", json!({ "username": "u", "store": "file" }));
+        let passphrase = Some("passphrase".to_string());
+        let password_path = reader.path().with_extension("password");
+        let mut file = FileStore::new(password_path.clone(), PathBuf::new(), passphrase);
+        file.set("p").unwrap();
+        reader.store = Box::new(file);
+        reader.set_value("store", json!("memory")).unwrap();
+        assert_eq!(reader.kind, Some(StoreKind::Memory));
+        assert_eq!(reader.store.get().unwrap().expose(), "p");
+        assert!(!password_path.exists());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::StoreKind;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tunet_helper::{billing::*, *};
+
+pub const SETTINGS_VERSION: u64 = 2;
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Preferences {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<StoreKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub portal: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_alert: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    #[serde(flatten)]
+    pub http: HttpClientOptions,
+}
+
+impl Preferences {
+    /// 可以通过 `config set` 修改的设置项，与上面的字段一一对应。
+    pub const KEYS: &'static [&'static str] = &[
+        "store",
+        "password_command",
+        "host",
+        "portal",
+        "refresh",
+        "color",
+        "alerts",
+        "balance_alert",
+        "tiers",
+        "cap",
+        "hooks",
+        "retry",
+        "connect_timeout",
+        "timeout",
+        "proxy",
+        "local_address",
+        "user_agent",
+    ];
+
+    pub fn host(&self) -> Result<Option<NetState>> {
+        self.host.as_deref().map(str::parse).transpose()
+    }
+
+    pub fn portal(&self) -> Result<Option<PortalConfig>> {
+        self.portal.as_deref().map(PortalConfig::load).transpose()
+    }
+
+    pub fn flux_alerts(&self) -> Result<Vec<Flux>> {
+        self.alerts.iter().map(|s| s.parse()).collect()
+    }
+
+    pub fn tariff(&self) -> Result<Tariff> {
+        let tiers = self
+            .tiers
+            .iter()
+            .map(|s| s.parse())
+            .collect::<Result<Vec<TariffTier>>>()?;
+        Ok(Tariff::default().with_overrides(&tiers, self.cap))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.host()?;
+        self.portal()?;
+        self.flux_alerts()?;
+        self.tariff()?;
+        self.http.build()?;
+        match self.color.as_deref() {
+            None | Some("auto") | Some("always") | Some("never") => Ok(()),
+            Some(c) => Err(anyhow::anyhow!("无效的颜色设置：{}", c)),
+        }
+    }
+}
+
+pub(crate) fn migrate(mut value: JsonValue) -> Result<JsonValue> {
+    let version = value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or_default();
+    if version > SETTINGS_VERSION {
+        return Err(anyhow::anyhow!("不支持的设置文件版本：{}", version));
+    }
+    if let Some(map) = value.as_object_mut() {
+        if version < 1 {
+            // Version 0 always wrote an empty password placeholder.
+            if map.get("password").and_then(|p| p.as_str()) == Some("") {
+                map.remove("password");
+            }
+        }
+        if version < 2 {
+            // Version 1 stored a flat list of ac_ids for all networks.
+            if let Some(ids) = map.get("ac_ids").and_then(|ids| ids.as_array()) {
+                let ids = ids
+                    .iter()
+                    .filter_map(|id| id.as_i64())
+                    .map(|id| id as i32)
+                    .collect::<Vec<_>>();
+                map.insert(
+                    "ac_ids".to_string(),
+                    serde_json::to_value(AcIdCache::from(ids))?,
+                );
+            }
+        }
+        map.insert("version".to_string(), SETTINGS_VERSION.into());
+    }
+    Ok(value)
+}
@@ -0,0 +1,291 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use keyring::Keyring;
+use serde::{Deserialize, Serialize};
+use std::fs::{remove_file, DirBuilder, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tunet_helper::*;
+
+pub trait CredentialStore: Send {
+    fn get(&self) -> Result<Secret>;
+    fn set(&mut self, value: &str) -> Result<()>;
+    fn delete(&mut self) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StoreKind {
+    #[default]
+    Keyring,
+    File,
+    Memory,
+}
+
+impl std::str::FromStr for StoreKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keyring" => Ok(Self::Keyring),
+            "file" => Ok(Self::File),
+            "memory" => Ok(Self::Memory),
+            _ => Err(anyhow::anyhow!("无效的密码存储方式：{}", s)),
+        }
+    }
+}
+
+pub struct KeyringStore(Keyring);
+
+impl KeyringStore {
+    pub fn new(key: &str) -> Result<Self> {
+        Ok(Self(Keyring::new(key)?))
+    }
+}
+
+// The error type differs between platforms.
+#[allow(clippy::needless_question_mark)]
+impl CredentialStore for KeyringStore {
+    fn get(&self) -> Result<Secret> {
+        Ok(self.0.get()?.into())
+    }
+
+    fn set(&mut self, value: &str) -> Result<()> {
+        self.0.set(value)?;
+        Ok(())
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        Ok(self.0.delete()?)
+    }
+}
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+const MODE_MACHINE_KEY: u8 = 0;
+const MODE_PASSPHRASE: u8 = 1;
+
+pub static PASSPHRASE_ENV: &str = "TUNET_PASSPHRASE";
+
+/// 加密保存在文件中的密码。
+///
+/// 设置了口令时，密钥由口令经 Argon2 派生。否则密钥随机生成，保存在同一目录的
+/// `secret.key` 中，这只是一种混淆：能读取这两个文件的人就能解密密码。
+pub struct FileStore {
+    path: PathBuf,
+    key_path: PathBuf,
+    passphrase: Option<String>,
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf, key_path: PathBuf, passphrase: Option<String>) -> Self {
+        Self {
+            path,
+            key_path,
+            passphrase,
+        }
+    }
+
+    fn machine_key(&self, create: bool) -> Result<[u8; 32]> {
+        if let Ok(data) = std::fs::read(&self.key_path) {
+            if let Ok(key) = data.try_into() {
+                return Ok(key);
+            }
+        }
+        if !create {
+            return Err(anyhow::anyhow!("找不到密钥文件"));
+        }
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        write_private(&self.key_path, &key)?;
+        Ok(key)
+    }
+
+    fn key(&self, mode: u8, salt: &[u8], create: bool) -> Result<[u8; 32]> {
+        match mode {
+            MODE_MACHINE_KEY => self.machine_key(create),
+            MODE_PASSPHRASE => {
+                let passphrase = self
+                    .passphrase
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("需要口令才能解密密码"))?;
+                let mut key = [0u8; 32];
+                Argon2::default()
+                    .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                Ok(key)
+            }
+            _ => Err(anyhow::anyhow!("无法识别的密码文件")),
+        }
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn get(&self) -> Result<Secret> {
+        let data = std::fs::read(&self.path)?;
+        if data.len() < 1 + SALT_LEN + NONCE_LEN {
+            return Err(anyhow::anyhow!("无法识别的密码文件"));
+        }
+        let (salt, rest) = data[1..].split_at(SALT_LEN);
+        let (nonce, cipher) = rest.split_at(NONCE_LEN);
+        let key = self.key(data[0], salt, false)?;
+        let plain = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(Nonce::from_slice(nonce), cipher)
+            .map_err(|_| anyhow::anyhow!("密码解密失败"))?;
+        Ok(String::from_utf8(plain)?.into())
+    }
+
+    fn set(&mut self, value: &str) -> Result<()> {
+        let mode = if self.passphrase.is_some() {
+            MODE_PASSPHRASE
+        } else {
+            MODE_MACHINE_KEY
+        };
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let key = self.key(mode, &salt, true)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(Nonce::from_slice(&nonce), value.as_bytes())
+            .map_err(|_| anyhow::anyhow!("密码加密失败"))?;
+        let mut data = vec![mode];
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&cipher);
+        write_private(&self.path, &data)
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        if self.path.exists() {
+            remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(p) = path.parent() {
+        DirBuilder::new().recursive(true).create(p)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // 已存在的文件不会应用上面的权限。
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(data)?;
+    Ok(())
+}
+
+/// 生成随机令牌，写入只有当前用户可读写的文件。
+pub fn write_token(path: &Path) -> Result<String> {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    write_private(path, token.as_bytes())?;
+    Ok(token)
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStore(Option<Secret>);
+
+impl CredentialStore for MemoryStore {
+    fn get(&self) -> Result<Secret> {
+        self.0.clone().ok_or_else(|| anyhow::anyhow!("未保存密码"))
+    }
+
+    fn set(&mut self, value: &str) -> Result<()> {
+        self.0 = Some(value.to_string().into());
+        Ok(())
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        self.0 = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tunet-store-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        DirBuilder::new().recursive(true).create(&dir).unwrap();
+        dir
+    }
+
+    fn file_store(dir: &Path, passphrase: Option<&str>) -> FileStore {
+        FileStore::new(
+            dir.join("settings.password"),
+            dir.join("secret.key"),
+            passphrase.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn machine_key() {
+        let dir = temp_dir("machine");
+        file_store(&dir, None).set("p@ss").unwrap();
+        assert!(dir.join("secret.key").exists());
+        assert_eq!(file_store(&dir, None).get().unwrap().expose(), "p@ss");
+    }
+
+    #[test]
+    fn passphrase() {
+        let dir = temp_dir("passphrase");
+        file_store(&dir, Some("abc")).set("p@ss").unwrap();
+        assert!(!dir.join("secret.key").exists());
+        assert_eq!(
+            file_store(&dir, Some("abc")).get().unwrap().expose(),
+            "p@ss"
+        );
+        assert!(file_store(&dir, Some("abd")).get().is_err());
+        assert!(file_store(&dir, None).get().is_err());
+    }
+
+    #[test]
+    fn tampered() {
+        for (name, passphrase) in [("tamper-machine", None), ("tamper-passphrase", Some("abc"))] {
+            let dir = temp_dir(name);
+            let mut store = file_store(&dir, passphrase);
+            store.set("p@ss").unwrap();
+            let path = dir.join("settings.password");
+            let mut data = std::fs::read(&path).unwrap();
+            *data.last_mut().unwrap() ^= 1;
+            std::fs::write(&path, &data).unwrap();
+            assert!(store.get().is_err());
+            std::fs::write(&path, &data[..SALT_LEN]).unwrap();
+            assert!(store.get().is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("permissions");
+        let path = dir.join("token");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_token(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
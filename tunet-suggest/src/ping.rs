@@ -1,7 +1,9 @@
 use tunet_helper::*;
 
 async fn can_connect(client: &HttpClient, uri: &str) -> bool {
-    client.head(uri).send().await.is_ok()
+    let ok = client.head(uri).send().await.is_ok();
+    tracing::debug!(uri, ok, "探测连接");
+    ok
 }
 
 pub async fn suggest(client: &HttpClient) -> NetState {
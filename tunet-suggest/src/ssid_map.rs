@@ -1,9 +1,13 @@
 use crate::ping;
+#[cfg(feature = "netstatus")]
 use lazy_static::lazy_static;
+#[cfg(feature = "netstatus")]
 use netstatus::NetStatus;
+#[cfg(feature = "netstatus")]
 use std::collections::BTreeMap;
 use tunet_helper::*;
 
+#[cfg(feature = "netstatus")]
 lazy_static! {
     static ref SUGGEST_SSID_MAP: BTreeMap<&'static str, NetState> = {
         let mut map = BTreeMap::new();
@@ -17,10 +21,24 @@ lazy_static! {
     };
 }
 
+#[cfg(feature = "netstatus")]
 pub async fn suggest(client: &HttpClient) -> NetState {
-    suggest_with_status(client, NetStatus::current()).await
+    let status = tokio::task::spawn_blocking(NetStatus::current)
+        .await
+        .unwrap_or(NetStatus::Unknown);
+    suggest_with_status(client, status).await
 }
 
+/// 未启用 `netstatus` 特性时只能通过访问各个地址推荐连接方式。
+#[cfg(not(feature = "netstatus"))]
+pub async fn suggest(client: &HttpClient) -> NetState {
+    let state = ping::suggest(client).await;
+    tracing::debug!(?state, "推荐的连接方式");
+    state
+}
+
+#[cfg(feature = "netstatus")]
+#[tracing::instrument(skip(client), fields(status = %s))]
 pub async fn suggest_with_status(client: &HttpClient, s: NetStatus) -> NetState {
     let state = match s {
         NetStatus::Unknown => None,
@@ -28,8 +46,10 @@ pub async fn suggest_with_status(client: &HttpClient, s: NetStatus) -> NetState
         NetStatus::Wlan(ssid) => SUGGEST_SSID_MAP.get(ssid.as_str()).copied(),
         NetStatus::Lan => Some(NetState::Auth4),
     };
-    match state {
+    let state = match state {
         Some(state) => state,
         None => ping::suggest(client).await,
-    }
+    };
+    tracing::debug!(?state, "推荐的连接方式");
+    state
 }
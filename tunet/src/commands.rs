@@ -1,19 +1,36 @@
+use crate::config::Config;
+#[cfg(feature = "daemon")]
+use crate::daemon::Daemon;
+use crate::debug::DebugTools;
+#[cfg(feature = "usereg")]
+use crate::exporter::Exporter;
+#[cfg(feature = "usereg")]
+use crate::usereg::{Detail, Online, UseregConnect, UseregDrop};
 use async_trait::async_trait;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use enum_dispatch::enum_dispatch;
-use futures_util::{pin_mut, stream::TryStreamExt};
-use itertools::Itertools;
-use mac_address::MacAddressIterator;
-use std::net::Ipv4Addr;
+#[cfg(feature = "usereg")]
+use futures_util::stream::TryStreamExt;
+use std::ffi::OsString;
+use std::net::IpAddr;
 use std::sync::Arc;
-use std::{cmp::Reverse, ffi::OsString};
 use termcolor::{Color, ColorChoice, StandardStream};
 use termcolor_output as tco;
-use tunet_helper::{usereg::*, *};
+#[cfg(feature = "usereg")]
+use tunet_helper::usereg::*;
+use tunet_helper::{billing::*, *};
 use tunet_settings_cli::*;
 use tunet_suggest::TUNetHelperExt;
 
-fn get_flux_color(f: &Flux, total: bool) -> Color {
+pub fn color_choice() -> ColorChoice {
+    match read_preferences().color.as_deref() {
+        Some("always") => ColorChoice::Always,
+        Some("never") => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    }
+}
+
+pub fn get_flux_color(f: &Flux, total: bool) -> Color {
     let flux = f.0;
     if flux == 0 {
         Color::Cyan
@@ -31,8 +48,7 @@ pub trait TUNetCommand {
 }
 
 #[enum_dispatch]
-#[derive(Debug, Parser)]
-#[clap(about, version, author)]
+#[derive(Debug, Subcommand)]
 pub enum TUNet {
     #[clap(name = "login", about = "登录")]
     Login,
@@ -40,14 +56,28 @@ pub enum TUNet {
     Logout,
     #[clap(name = "status", about = "查看在线状态")]
     Status,
+    #[cfg(feature = "usereg")]
     #[clap(name = "online", about = "查询在线IP")]
     Online,
+    #[cfg(feature = "usereg")]
     #[clap(name = "connect", about = "上线IP")]
     UseregConnect,
+    #[cfg(feature = "usereg")]
     #[clap(name = "drop", about = "下线IP")]
     UseregDrop,
+    #[cfg(feature = "usereg")]
     #[clap(name = "detail", about = "流量明细")]
     Detail,
+    #[cfg(feature = "daemon")]
+    #[clap(name = "daemon", about = "启动本地控制服务")]
+    Daemon,
+    #[cfg(feature = "usereg")]
+    #[clap(name = "exporter", about = "导出 Prometheus 指标")]
+    Exporter,
+    #[clap(name = "config", about = "查看或修改设置")]
+    Config,
+    #[clap(name = "debug", about = "调试工具")]
+    DebugTools,
     #[clap(name = "deletecred", about = "删除用户名和密码")]
     DeleteCred,
     #[clap(name = "cui", about = "启动命令行界面")]
@@ -61,14 +91,22 @@ pub struct Login {
     #[clap(long, short = 's')]
     /// 连接方式
     host: Option<NetState>,
+    #[clap(long)]
+    /// 要认证的设备 IP，仅支持 auth4/auth6
+    ip: Option<IpAddr>,
 }
 
 #[async_trait]
 impl TUNetCommand for Login {
     async fn run(&self) -> Result<()> {
-        let client = create_http_client()?;
+        let client = http_client()?;
         let cred = read_cred()?;
-        let c = TUNetConnect::new_with_suggest(self.host, cred, client).await?;
+        let c = TUNetConnect::new_with_suggest(self.host.or_else(read_host), cred, client)
+            .await?
+            .with_portal(read_portal()?)
+            .with_retry(read_retry())
+            .with_fixtures(read_fixtures())
+            .with_ip(self.ip)?;
         let res = c.login().await?;
         println!("{}", res);
         save_cred(c.cred()).await
@@ -80,15 +118,29 @@ pub struct Logout {
     #[clap(long, short = 's')]
     /// 连接方式
     host: Option<NetState>,
+    #[clap(long)]
+    /// 要注销的设备 IP，仅支持 auth4/auth6
+    ip: Option<IpAddr>,
+    #[clap(long, conflicts_with = "ip")]
+    /// 强制下线指定 IP 的会话，仅支持 auth4/auth6
+    drop: Option<IpAddr>,
 }
 
 #[async_trait]
 impl TUNetCommand for Logout {
     async fn run(&self) -> Result<()> {
-        let client = create_http_client()?;
+        let client = http_client()?;
         let cred = read_username()?;
-        let c = TUNetConnect::new_with_suggest(self.host, cred, client).await?;
-        let res = c.logout().await?;
+        let c = TUNetConnect::new_with_suggest(self.host.or_else(read_host), cred, client)
+            .await?
+            .with_portal(read_portal()?)
+            .with_retry(read_retry())
+            .with_fixtures(read_fixtures())
+            .with_ip(self.ip)?;
+        let res = match self.drop {
+            Some(target) => c.drop(target).await?,
+            None => c.logout().await?,
+        };
         println!("{}", res);
         Ok(())
     }
@@ -98,17 +150,71 @@ pub struct Status {
     #[clap(long, short = 's')]
     /// 连接方式
     host: Option<NetState>,
+    #[clap(long, short = 'p')]
+    /// 预测方式，linear 或 average，默认为 linear
+    projection: Option<ProjectionMethod>,
+    #[clap(long = "tier")]
+    /// 计费档位，如 20G:1.0
+    tiers: Vec<TariffTier>,
+    #[clap(long)]
+    /// 每月费用上限
+    cap: Option<f64>,
+}
+
+impl Status {
+    fn projection(&self) -> ProjectionMethod {
+        self.projection.unwrap_or_default()
+    }
+
+    #[cfg(feature = "usereg")]
+    async fn details(&self, client: HttpClient) -> Result<Vec<NetDetail>> {
+        match self.projection() {
+            ProjectionMethod::Linear => Ok(Vec::new()),
+            _ => {
+                let cred = read_cred()?;
+                let c = UseregHelper::new(cred, client)
+                    .with_retry(read_retry())
+                    .with_fixtures(read_fixtures());
+                c.login().await?;
+                let details = c
+                    .details(NetDetailOrder::LogoutTime, false)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                save_cred(c.cred()).await?;
+                Ok(details)
+            }
+        }
+    }
+
+    #[cfg(not(feature = "usereg"))]
+    async fn details(&self, _client: HttpClient) -> Result<Vec<NetDetail>> {
+        match self.projection() {
+            ProjectionMethod::Linear => Ok(Vec::new()),
+            _ => Err(anyhow::anyhow!("未启用 usereg，仅支持线性预测")),
+        }
+    }
 }
 
 #[async_trait]
 impl TUNetCommand for Status {
     async fn run(&self) -> Result<()> {
-        let client = create_http_client()?;
-        let c =
-            TUNetConnect::new_with_suggest(self.host, Arc::new(NetCredential::default()), client)
-                .await?;
+        let client = http_client()?;
+        let c = TUNetConnect::new_with_suggest(
+            self.host.or_else(read_host),
+            Arc::new(NetCredential::default()),
+            client.clone(),
+        )
+        .await?
+        .with_portal(read_portal()?)
+        .with_retry(read_retry())
+        .with_fixtures(read_fixtures());
         let f = c.flux().await?;
-        let stdout = StandardStream::stdout(ColorChoice::Auto);
+        let details = self.details(client).await?;
+        let e = read_preferences()
+            .tariff()?
+            .with_overrides(&self.tiers, self.cap)
+            .estimate(f.flux, &details, self.projection(), campus_now());
+        let stdout = StandardStream::stdout(color_choice());
         let mut stdout = tco::ResetGuard::Owned(stdout);
         tco::writeln!(
             stdout,
@@ -139,208 +245,23 @@ impl TUNetCommand for Status {
             fg!(Some(Color::Yellow)),
             f.balance
         )?;
-        Ok(())
-    }
-}
-
-#[derive(Debug, Parser)]
-pub struct Online {}
-
-#[async_trait]
-impl TUNetCommand for Online {
-    async fn run(&self) -> Result<()> {
-        let client = create_http_client()?;
-        let cred = read_cred()?;
-        let c = UseregHelper::new(cred, client);
-        c.login().await?;
-        let us = c.users();
-        let mac_addrs = MacAddressIterator::new()
-            .map(|it| it.collect::<Vec<_>>())
-            .unwrap_or_default();
-        let stdout = StandardStream::stdout(ColorChoice::Auto);
-        let mut stdout = tco::ResetGuard::Owned(stdout);
         tco::writeln!(
             stdout,
-            "    IP地址            登录时间         流量        MAC地址"
-        )?;
-
-        pin_mut!(us);
-        while let Some(u) = us.try_next().await? {
-            let is_self = mac_addrs
-                .iter()
-                .any(|it| Some(it) == u.mac_address.as_ref());
-            tco::writeln!(
-                stdout,
-                "{}{:15} {}{:20} {}{:>8} {}{} {}{}",
-                fg!(Some(Color::Yellow)),
-                u.address,
-                fg!(Some(Color::Green)),
-                u.login_time,
-                fg!(Some(get_flux_color(&u.flux, true))),
-                u.flux,
-                fg!(Some(Color::Cyan)),
-                u.mac_address.map(|a| a.to_string()).unwrap_or_default(),
-                fg!(Some(Color::Magenta)),
-                if is_self { "本机" } else { "" }
-            )?;
-        }
-        save_cred(c.cred()).await
-    }
-}
-
-#[derive(Debug, Parser)]
-pub struct UseregConnect {
-    #[clap(long, short)]
-    /// IP地址
-    address: Ipv4Addr,
-}
-
-#[async_trait]
-impl TUNetCommand for UseregConnect {
-    async fn run(&self) -> Result<()> {
-        let client = create_http_client()?;
-        let cred = read_cred()?;
-        let c = UseregHelper::new(cred, client);
-        c.login().await?;
-        let res = c.connect(self.address).await?;
-        println!("{}", res);
-        save_cred(c.cred()).await
-    }
-}
-
-#[derive(Debug, Parser)]
-pub struct UseregDrop {
-    #[clap(long, short)]
-    /// IP地址
-    address: Ipv4Addr,
-}
-
-#[async_trait]
-impl TUNetCommand for UseregDrop {
-    async fn run(&self) -> Result<()> {
-        let client = create_http_client()?;
-        let cred = read_cred()?;
-        let c = UseregHelper::new(cred, client);
-        c.login().await?;
-        let res = c.drop(self.address).await?;
-        println!("{}", res);
-        save_cred(c.cred()).await
-    }
-}
-
-#[derive(Debug, Parser)]
-pub struct Detail {
-    #[clap(long, short, default_value = "logout")]
-    /// 排序方式
-    order: NetDetailOrder,
-    #[clap(long, short)]
-    /// 倒序
-    descending: bool,
-    #[clap(long, short)]
-    /// 按日期分组
-    grouping: bool,
-}
-
-impl Detail {
-    async fn run_detail(&self) -> Result<()> {
-        let client = create_http_client()?;
-        let cred = read_cred()?;
-        let c = UseregHelper::new(cred, client);
-        c.login().await?;
-        let details = c.details(self.order, self.descending);
-        let stdout = StandardStream::stdout(ColorChoice::Auto);
-        let mut stdout = tco::ResetGuard::Owned(stdout);
-        tco::writeln!(stdout, "      登录时间             注销时间         流量")?;
-        let mut total_flux = Flux(0);
-
-        pin_mut!(details);
-        while let Some(d) = details.try_next().await? {
-            tco::writeln!(
-                stdout,
-                "{}{:20} {:20} {}{:>8}",
-                fg!(Some(Color::Green)),
-                d.login_time,
-                d.logout_time,
-                fg!(Some(get_flux_color(&d.flux, false))),
-                d.flux
-            )?;
-            total_flux.0 += d.flux.0;
-        }
-        tco::writeln!(
-            stdout,
-            "{}总流量 {}{}{}",
+            "{}费用 {}{}",
             fg!(Some(Color::Cyan)),
-            fg!(Some(get_flux_color(&total_flux, true))),
-            bold!(true),
-            total_flux
+            fg!(Some(Color::Yellow)),
+            e.charge
         )?;
-        save_cred(c.cred()).await
-    }
-
-    async fn run_detail_grouping(&self) -> Result<()> {
-        let client = create_http_client()?;
-        let cred = read_cred()?;
-        let c = UseregHelper::new(cred, client);
-        c.login().await?;
-        let details = c
-            .details(NetDetailOrder::LogoutTime, self.descending)
-            .try_collect::<Vec<_>>()
-            .await?;
-        let mut details = details
-            .into_iter()
-            .group_by(|detail| detail.logout_time.date())
-            .into_iter()
-            .map(|(key, group)| (key, Flux(group.map(|detail| detail.flux.0).sum::<u64>())))
-            .collect::<Vec<_>>();
-        match self.order {
-            NetDetailOrder::Flux => {
-                if self.descending {
-                    details.sort_unstable_by_key(|(_, flux)| Reverse(*flux));
-                } else {
-                    details.sort_unstable_by_key(|(_, flux)| *flux);
-                }
-            }
-            _ => {
-                if self.descending {
-                    details.sort_unstable_by_key(|(date, _)| Reverse(date.day()));
-                }
-            }
-        }
-        let stdout = StandardStream::stdout(ColorChoice::Auto);
-        let mut stdout = tco::ResetGuard::Owned(stdout);
-        tco::writeln!(stdout, " 登录日期    流量")?;
-        let mut total_flux = Flux(0);
-        for (date, flux) in details {
-            tco::writeln!(
-                stdout,
-                "{}{:10} {}{:>8}",
-                fg!(Some(Color::Green)),
-                date,
-                fg!(Some(get_flux_color(&flux, true))),
-                flux
-            )?;
-            total_flux.0 += flux.0;
-        }
         tco::writeln!(
             stdout,
-            "{}总流量 {}{}{}",
+            "{}预计 {}{} {}{}",
             fg!(Some(Color::Cyan)),
-            fg!(Some(get_flux_color(&total_flux, true))),
-            bold!(true),
-            total_flux
+            fg!(Some(get_flux_color(&e.projected_flux, true))),
+            e.projected_flux,
+            fg!(Some(Color::Yellow)),
+            e.projected_charge
         )?;
-        save_cred(c.cred()).await
-    }
-}
-
-#[async_trait]
-impl TUNetCommand for Detail {
-    async fn run(&self) -> Result<()> {
-        if self.grouping {
-            self.run_detail_grouping().await
-        } else {
-            self.run_detail().await
-        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,74 @@
+use crate::commands::TUNetCommand;
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use serde_json::Value as JsonValue;
+use tunet_helper::*;
+#[cfg(feature = "daemon")]
+use tunet_model::Hook;
+use tunet_settings_cli::*;
+
+#[derive(Debug, Parser)]
+pub struct Config {
+    #[clap(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    #[clap(name = "path", about = "显示设置文件路径")]
+    Path,
+    #[clap(name = "list", about = "列出所有设置")]
+    List,
+    #[clap(name = "get", about = "读取设置项")]
+    Get {
+        /// 设置项名称
+        key: String,
+    },
+    #[clap(name = "set", about = "修改设置项，值为 null 时删除")]
+    Set {
+        /// 设置项名称
+        key: String,
+        /// 设置值，按 JSON 解析，失败时作为字符串
+        value: String,
+    },
+}
+
+fn print_value(value: &JsonValue) {
+    match value {
+        JsonValue::String(s) => println!("{}", s),
+        v => println!("{}", v),
+    }
+}
+
+#[async_trait]
+impl TUNetCommand for Config {
+    async fn run(&self) -> Result<()> {
+        let mut reader = FileSettingsReader::new()?;
+        match &self.command {
+            ConfigCommand::Path => println!("{}", reader.path().display()),
+            ConfigCommand::List => {
+                for (key, value) in reader.values()? {
+                    print!("{} = ", key);
+                    print_value(&value);
+                }
+            }
+            ConfigCommand::Get { key } => match reader.get_value(key)? {
+                Some(value) => print_value(&value),
+                None => return Err(anyhow::anyhow!("未设置：{}", key)),
+            },
+            ConfigCommand::Set { key, value } => {
+                let value = serde_json::from_str(value)
+                    .unwrap_or_else(|_| JsonValue::String(value.clone()));
+                #[cfg(feature = "daemon")]
+                if key == "hooks" && !value.is_null() {
+                    serde_json::from_value::<Vec<String>>(value.clone())?
+                        .iter()
+                        .map(|h| h.parse::<Hook>())
+                        .collect::<Result<Vec<_>>>()?;
+                }
+                reader.set_value(key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,402 @@
+use crate::commands::TUNetCommand;
+use crate::exporter::{self, Metrics};
+use async_trait::async_trait;
+use clap::Parser;
+use serde_json::{json, Value as JsonValue};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tunet_helper::*;
+use tunet_model::{Action, Hook, Model, Task};
+use tunet_settings_cli::*;
+
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+const DEFAULT_REFRESH: u64 = 60;
+const MAX_REQUEST_HEAD: usize = 8192;
+const MAX_REQUEST_BODY: usize = 65536;
+
+#[derive(Debug, Parser)]
+pub struct Daemon {
+    #[clap(long, short = 's')]
+    /// 连接方式
+    host: Option<NetState>,
+    #[clap(long, short)]
+    /// 监听的本地 TCP 地址
+    listen: Option<SocketAddr>,
+    #[cfg(unix)]
+    #[clap(long)]
+    /// Unix 套接字路径
+    socket: Option<std::path::PathBuf>,
+    #[clap(long = "hook")]
+    /// 事件钩子，如 login=/path/to/script
+    hooks: Vec<Hook>,
+    #[clap(long)]
+    /// 刷新流量的间隔秒数，默认 60
+    refresh: Option<u64>,
+    #[clap(long)]
+    /// 在该地址上提供 Prometheus 指标，包括本服务的登录次数
+    metrics: Option<SocketAddr>,
+}
+
+#[derive(Clone)]
+struct Shared {
+    model: Arc<RwLock<Model>>,
+    token: Option<Arc<str>>,
+}
+
+impl Shared {
+    fn new(
+        host: Option<NetState>,
+        cred: Arc<NetCredential>,
+        hooks: Vec<Hook>,
+        refresh: Option<u64>,
+    ) -> Result<Self> {
+        let (tx, mut rx) = mpsc::channel(32);
+        let mut model = Model::new(tx)?;
+        model.notify = true;
+        let prefs = read_preferences();
+        model.load_preferences(&prefs)?;
+        model.http = http_client()?;
+        if !hooks.is_empty() {
+            model.hooks = hooks;
+        }
+        model.refresh = refresh
+            .or(prefs.refresh)
+            .or(Some(DEFAULT_REFRESH))
+            .filter(|r| *r > 0);
+        let model = Arc::new(RwLock::new(model));
+        {
+            let model = model.clone();
+            tokio::spawn(async move {
+                while let Some(a) = rx.recv().await {
+                    model.write().unwrap().handle(a);
+                }
+            });
+        }
+        {
+            let model = model.read().unwrap();
+            model.queue(Action::Credential(cred));
+            model.queue(Action::State(host));
+            model.queue(Action::Timer);
+        }
+        Ok(Self { model, token: None })
+    }
+
+    async fn run(&self, start: impl FnOnce(&mut Model) -> Option<Task>) -> Result<()> {
+        let task = start(&mut self.model.write().unwrap())
+            .ok_or_else(|| anyhow::anyhow!("正在进行其他操作"))?;
+        tokio::time::timeout(REQUEST_TIMEOUT, task)
+            .await
+            .map_err(|_| anyhow::anyhow!("操作超时"))??
+    }
+
+    fn authorized(&self, token: Option<&str>) -> bool {
+        match &self.token {
+            Some(t) => token == Some(t),
+            None => true,
+        }
+    }
+
+    async fn handle(&self, req: &JsonValue) -> Result<JsonValue> {
+        if !self.authorized(req["token"].as_str()) {
+            return Err(anyhow::anyhow!("令牌无效"));
+        }
+        let action = req["action"].as_str().unwrap_or_default();
+        match action {
+            "login" => self.run(Model::login).await?,
+            "logout" => self.run(Model::logout).await?,
+            "flux" => self.run(|m| m.flux()).await?,
+            "online" => self.run(|m| m.online()).await?,
+            "connect" => {
+                let addr = address(req)?;
+                self.run(|m| m.connect(addr)).await?
+            }
+            "drop" => {
+                let addr = address(req)?;
+                self.run(|m| m.drop(addr)).await?
+            }
+            "status" => {}
+            _ => return Err(anyhow::anyhow!("未知操作：{}", action)),
+        }
+        let model = self.model.read().unwrap();
+        Ok(match action {
+            "online" | "connect" | "drop" => json!({
+                "ok": true,
+                "users": model.users.iter().map(|u| json!({
+                    "address": u.address.to_string(),
+                    "login_time": u.login_time.to_rfc3339(),
+                    "flux": u.flux.0,
+                    "mac_address": u.mac_address.map(|a| a.to_string()),
+                })).collect::<Vec<_>>(),
+            }),
+            _ => json!({
+                "ok": true,
+                "state": model.state.to_string(),
+                "log": model.log,
+                "flux": {
+                    "username": model.flux.username,
+                    "flux": model.flux.flux.0,
+                    "online_time": model.flux.online_time.0.num_seconds(),
+                    "balance": model.flux.balance.0,
+                },
+            }),
+        })
+    }
+}
+
+fn address(req: &JsonValue) -> Result<Ipv4Addr> {
+    Ok(req["address"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("缺少 address"))?
+        .parse()?)
+}
+
+/// Unix 套接字上每行一个 JSON 请求，返回一行 JSON。
+#[cfg(unix)]
+async fn serve<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin>(
+    stream: S,
+    shared: Shared,
+) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let res = match serde_json::from_str::<JsonValue>(&line) {
+            Ok(req) => shared.handle(&req).await,
+            Err(e) => Err(e.into()),
+        };
+        let res = res.unwrap_or_else(|e| json!({ "ok": false, "error": e.to_string() }));
+        writer.write_all(format!("{}\n", res).as_bytes()).await?;
+    }
+    Ok(())
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+async fn read_http_request(stream: &mut TcpStream) -> Result<Option<HttpRequest>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let head_len = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let len = stream.read(&mut chunk).await?;
+        if len == 0 || buf.len() + len > MAX_REQUEST_HEAD {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..len]);
+    };
+    let head = String::from_utf8_lossy(&buf[..head_len]).into_owned();
+    let mut lines = head.lines();
+    let mut parts = lines.next().unwrap_or_default().split(' ');
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Ok(None),
+    };
+    let mut content_length = 0;
+    let mut token = None;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = match value.parse::<usize>() {
+                    Ok(len) if len <= MAX_REQUEST_BODY => len,
+                    _ => return Ok(None),
+                };
+            } else if name.eq_ignore_ascii_case("authorization") {
+                token = value.strip_prefix("Bearer ").map(|t| t.trim().to_string());
+            }
+        }
+    }
+    let mut body = buf.split_off(head_len);
+    if body.len() < content_length {
+        let start = body.len();
+        body.resize(content_length, 0);
+        stream.read_exact(&mut body[start..]).await?;
+    }
+    body.truncate(content_length);
+    Ok(Some(HttpRequest {
+        method,
+        path,
+        token,
+        body,
+    }))
+}
+
+/// 每个连接处理一个 `POST /<action>` 请求，请求体为可选的 JSON 对象。
+async fn serve_http(mut stream: TcpStream, shared: Shared) -> Result<()> {
+    let (status, res) = match read_http_request(&mut stream).await? {
+        None => ("400 Bad Request", Err(anyhow::anyhow!("无效的请求"))),
+        Some(req) if req.method != "POST" => (
+            "405 Method Not Allowed",
+            Err(anyhow::anyhow!("只支持 POST 请求")),
+        ),
+        Some(req) => {
+            let body = if req.body.iter().all(|b| b.is_ascii_whitespace()) {
+                Ok(json!({}))
+            } else {
+                serde_json::from_slice::<JsonValue>(&req.body).map_err(anyhow::Error::from)
+            };
+            match body {
+                Ok(JsonValue::Object(mut map)) => {
+                    let token = req
+                        .token
+                        .or_else(|| map.get("token").and_then(|t| t.as_str()).map(String::from));
+                    if !shared.authorized(token.as_deref()) {
+                        ("401 Unauthorized", Err(anyhow::anyhow!("令牌无效")))
+                    } else {
+                        let action = req.path.trim_start_matches('/');
+                        map.insert("action".to_string(), action.into());
+                        if let Some(token) = token {
+                            map.insert("token".to_string(), token.into());
+                        }
+                        match shared.handle(&JsonValue::Object(map)).await {
+                            Ok(res) => ("200 OK", Ok(res)),
+                            Err(e) => ("400 Bad Request", Err(e)),
+                        }
+                    }
+                }
+                Ok(_) => (
+                    "400 Bad Request",
+                    Err(anyhow::anyhow!("请求体应为 JSON 对象")),
+                ),
+                Err(e) => ("400 Bad Request", Err(e)),
+            }
+        }
+    };
+    let body = res
+        .unwrap_or_else(|e| json!({ "ok": false, "error": e.to_string() }))
+        .to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+impl Daemon {
+    async fn listen_tcp(&self, addr: SocketAddr, mut shared: Shared) -> Result<()> {
+        if !addr.ip().is_loopback() {
+            return Err(anyhow::anyhow!("只允许监听本地地址"));
+        }
+        let token_path = FileSettingsReader::data_path(TOKEN_FILE)?;
+        shared.token = Some(write_token(&token_path)?.into());
+        let listener = TcpListener::bind(addr).await?;
+        println!("正在监听 http://{}/", listener.local_addr()?);
+        println!("令牌已写入 {}", token_path.display());
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let shared = shared.clone();
+            tokio::spawn(async move { serve_http(stream, shared).await.ok() });
+        }
+    }
+
+    #[cfg(unix)]
+    async fn listen_unix(&self, shared: Shared) -> Result<()> {
+        let path = match &self.socket {
+            Some(p) => p.clone(),
+            None => match std::env::var_os("XDG_RUNTIME_DIR").filter(|p| !p.is_empty()) {
+                Some(p) => std::path::PathBuf::from(p).join("tunet.sock"),
+                None => FileSettingsReader::data_path("tunet.sock")?,
+            },
+        };
+        let listener = bind_unix(&path)?;
+        println!("正在监听 {}", path.display());
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let shared = shared.clone();
+            tokio::spawn(async move { serve(stream, shared).await.ok() });
+        }
+    }
+}
+
+/// 在私有目录中绑定并设置权限，再移动到目标路径，避免其他用户抢先连接。
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| std::path::Path::new("."));
+    std::fs::DirBuilder::new().recursive(true).create(parent)?;
+    let dir = parent.join(format!(".tunet-{}", std::process::id()));
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let temp = dir.join("tunet.sock");
+    let res = (|| {
+        let uid = std::fs::metadata(&dir)?.uid();
+        let listener = tokio::net::UnixListener::bind(&temp)?;
+        std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o600))?;
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() || meta.uid() != uid {
+                return Err(anyhow::anyhow!("拒绝覆盖 {}", path.display()));
+            }
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(anyhow::anyhow!("已有服务在监听 {}", path.display()));
+            }
+            std::fs::remove_file(path)?;
+        }
+        std::fs::rename(&temp, path)?;
+        Ok(listener)
+    })();
+    std::fs::remove_file(&temp).ok();
+    std::fs::remove_dir(&dir).ok();
+    res
+}
+
+static TOKEN_FILE: &str = "daemon.token";
+
+#[cfg(not(unix))]
+static DEFAULT_LISTEN: &str = "127.0.0.1:9101";
+
+#[async_trait]
+impl TUNetCommand for Daemon {
+    async fn run(&self) -> Result<()> {
+        let cred = read_cred()?;
+        let host = self.host.or_else(read_host);
+        let shared = Shared::new(host, cred.clone(), self.hooks.clone(), self.refresh)?;
+        if let Some(addr) = self.metrics {
+            let model = shared.model.clone();
+            let metrics = Metrics::new(
+                host,
+                cred,
+                exporter::DEFAULT_INTERVAL,
+                Some(Box::new(move || {
+                    let model = model.read().unwrap();
+                    (model.login_success, model.login_failure)
+                })),
+            )?;
+            tokio::spawn(async move {
+                if let Err(e) = exporter::listen(addr, metrics).await {
+                    tracing::error!("无法提供指标：{}", e);
+                }
+            });
+        }
+        if let Some(addr) = self.listen {
+            return self.listen_tcp(addr, shared).await;
+        }
+        #[cfg(unix)]
+        {
+            self.listen_unix(shared).await
+        }
+        #[cfg(not(unix))]
+        {
+            self.listen_tcp(DEFAULT_LISTEN.parse()?, shared).await
+        }
+    }
+}
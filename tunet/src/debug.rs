@@ -0,0 +1,40 @@
+use crate::commands::TUNetCommand;
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use tunet_helper::*;
+use tunet_settings_cli::*;
+
+#[derive(Debug, Parser)]
+pub struct DebugTools {
+    #[clap(subcommand)]
+    command: DebugCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum DebugCommand {
+    #[clap(name = "decode-info", about = "解码登录请求中的 info 字段")]
+    DecodeInfo {
+        /// 以 {SRBX1} 开头的 info 字段
+        info: String,
+        #[clap(long, short)]
+        /// 登录时获取的 challenge
+        token: String,
+        #[clap(long)]
+        /// 使用 auth6 的门户配置
+        auth6: bool,
+    },
+}
+
+#[async_trait]
+impl TUNetCommand for DebugTools {
+    async fn run(&self) -> Result<()> {
+        match &self.command {
+            DebugCommand::DecodeInfo { info, token, auth6 } => {
+                let portal = read_portal()?.unwrap_or_default();
+                let portal = if *auth6 { &portal.auth6 } else { &portal.auth4 };
+                println!("{}", decode_info(info, token, portal)?);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use crate::commands::TUNetCommand;
+use async_trait::async_trait;
+use clap::Parser;
+use futures_util::TryStreamExt;
+use itertools::Itertools;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tunet_helper::{usereg::*, *};
+use tunet_settings_cli::*;
+use tunet_suggest::TUNetHelperExt;
+
+const MAX_REQUEST_HEAD: usize = 8192;
+
+pub const DEFAULT_INTERVAL: u64 = 60;
+
+#[derive(Debug, Parser)]
+pub struct Exporter {
+    #[clap(long, short, default_value = "127.0.0.1:9100")]
+    /// 监听地址
+    listen: SocketAddr,
+    #[clap(long, short = 's')]
+    /// 连接方式
+    host: Option<NetState>,
+    #[clap(long, default_value_t = DEFAULT_INTERVAL)]
+    /// 重新查询在线状态与用户管理页面的最短间隔秒数
+    interval: u64,
+}
+
+/// 门户登录成功与失败的次数。
+pub type LoginCounts = Box<dyn Fn() -> (u64, u64) + Send + Sync>;
+
+pub struct Metrics {
+    host: Option<NetState>,
+    portal: Option<Arc<PortalConfig>>,
+    retry: RetryPolicy,
+    cred: Arc<NetCredential>,
+    client: HttpClient,
+    interval: Duration,
+    cache: Mutex<Option<(Instant, String)>>,
+    flux_errors: AtomicU64,
+    usereg_errors: AtomicU64,
+    logins: Option<LoginCounts>,
+}
+
+impl Metrics {
+    pub fn new(
+        host: Option<NetState>,
+        cred: Arc<NetCredential>,
+        interval: u64,
+        logins: Option<LoginCounts>,
+    ) -> Result<Self> {
+        Ok(Self {
+            host,
+            portal: read_portal()?,
+            retry: read_retry(),
+            cred,
+            client: http_client()?,
+            interval: Duration::from_secs(interval),
+            cache: Mutex::new(None),
+            flux_errors: AtomicU64::new(0),
+            usereg_errors: AtomicU64::new(0),
+            logins,
+        })
+    }
+
+    async fn gather(&self) -> String {
+        let mut cache = self.cache.lock().await;
+        let fresh = matches!(&*cache, Some((time, _)) if time.elapsed() < self.interval);
+        if !fresh {
+            let mut buf = String::new();
+            self.write_flux(&mut buf).await;
+            self.write_usereg(&mut buf).await;
+            *cache = Some((Instant::now(), buf));
+        }
+        let mut buf = cache.as_ref().map(|(_, s)| s.clone()).unwrap_or_default();
+        drop(cache);
+        self.write_counters(&mut buf);
+        buf
+    }
+
+    async fn write_flux(&self, buf: &mut String) {
+        let flux = match TUNetConnect::new_with_suggest(
+            self.host,
+            Arc::new(NetCredential::default()),
+            self.client.clone(),
+        )
+        .await
+        {
+            Ok(c) => {
+                c.with_portal(self.portal.clone())
+                    .with_retry(self.retry.clone())
+                    .flux()
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let flux = match flux {
+            Ok(f) => Some(f),
+            Err(e) => {
+                tracing::warn!("无法获取在线状态：{}", e);
+                self.flux_errors.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        };
+        write_header(buf, "tunet_up", "gauge", "能否获取在线状态");
+        writeln!(buf, "tunet_up {}", flux.is_some() as u8).ok();
+        if let Some(f) = flux {
+            let label = format!("{{username=\"{}\"}}", escape(&f.username));
+            write_header(buf, "tunet_flux_bytes", "gauge", "本月已用流量");
+            writeln!(buf, "tunet_flux_bytes{} {}", label, f.flux.0).ok();
+            write_header(buf, "tunet_balance_yuan", "gauge", "账户余额");
+            writeln!(buf, "tunet_balance_yuan{} {}", label, f.balance.0).ok();
+            write_header(buf, "tunet_online_seconds", "gauge", "本次在线时长");
+            writeln!(
+                buf,
+                "tunet_online_seconds{} {}",
+                label,
+                f.online_time.0.num_seconds()
+            )
+            .ok();
+        }
+    }
+
+    async fn write_usereg(&self, buf: &mut String) {
+        let usereg = UseregHelper::new(self.cred.clone(), self.client.clone())
+            .with_retry(self.retry.clone());
+        if let Err(e) = usereg.login().await {
+            self.usereg_error(e);
+            return;
+        }
+        match usereg.users().try_collect::<Vec<_>>().await {
+            Ok(users) => {
+                write_header(buf, "tunet_online_devices", "gauge", "在线设备数");
+                writeln!(buf, "tunet_online_devices {}", users.len()).ok();
+            }
+            Err(e) => self.usereg_error(e),
+        }
+        match usereg
+            .details(NetDetailOrder::LogoutTime, false)
+            .try_collect::<Vec<_>>()
+            .await
+        {
+            Err(e) => self.usereg_error(e),
+            Ok(details) => {
+                write_header(
+                    buf,
+                    "tunet_detail_flux_bytes",
+                    "gauge",
+                    "按注销日期汇总的流量",
+                );
+                for (date, flux) in details
+                    .into_iter()
+                    .group_by(|detail| detail.logout_time.naive_local().date())
+                    .into_iter()
+                    .map(|(key, group)| (key, group.map(|detail| detail.flux.0).sum::<u64>()))
+                {
+                    writeln!(buf, "tunet_detail_flux_bytes{{date=\"{}\"}} {}", date, flux).ok();
+                }
+            }
+        }
+    }
+
+    fn usereg_error(&self, e: anyhow::Error) {
+        tracing::warn!("无法查询用户管理页面：{}", e);
+        self.usereg_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn write_counters(&self, buf: &mut String) {
+        write_header(
+            buf,
+            "tunet_flux_errors_total",
+            "counter",
+            "获取在线状态失败的次数",
+        );
+        writeln!(
+            buf,
+            "tunet_flux_errors_total {}",
+            self.flux_errors.load(Ordering::Relaxed)
+        )
+        .ok();
+        write_header(
+            buf,
+            "tunet_usereg_errors_total",
+            "counter",
+            "查询用户管理页面失败的次数",
+        );
+        writeln!(
+            buf,
+            "tunet_usereg_errors_total {}",
+            self.usereg_errors.load(Ordering::Relaxed)
+        )
+        .ok();
+        // 单独运行时本进程不登录，仍然导出为 0，以免查询时缺少序列。
+        let (success, failure) = self.logins.as_ref().map_or((0, 0), |logins| logins());
+        write_header(buf, "tunet_login_total", "counter", "门户登录的次数");
+        writeln!(buf, "tunet_login_total{{result=\"success\"}} {}", success).ok();
+        writeln!(buf, "tunet_login_total{{result=\"failure\"}} {}", failure).ok();
+    }
+}
+
+fn write_header(buf: &mut String, name: &str, ty: &str, help: &str) {
+    writeln!(buf, "# HELP {} {}", name, help).ok();
+    writeln!(buf, "# TYPE {} {}", name, ty).ok();
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+async fn read_request_path(stream: &mut TcpStream) -> Result<Option<String>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let len = stream.read(&mut chunk).await?;
+        if len == 0 || buf.len() + len > MAX_REQUEST_HEAD {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..len]);
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut parts = head.lines().next().unwrap_or_default().split(' ');
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => Ok(Some(path.to_string())),
+        _ => Ok(None),
+    }
+}
+
+async fn serve(mut stream: TcpStream, metrics: Arc<Metrics>) -> Result<()> {
+    let (status, body) = match read_request_path(&mut stream).await? {
+        Some(path) if path == "/metrics" => ("200 OK", metrics.gather().await),
+        Some(_) => ("404 Not Found", String::new()),
+        None => ("400 Bad Request", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+pub async fn listen(addr: SocketAddr, metrics: Metrics) -> Result<()> {
+    let metrics = Arc::new(metrics);
+    let listener = TcpListener::bind(addr).await?;
+    println!("正在监听 http://{}/metrics", listener.local_addr()?);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move { serve(stream, metrics).await.ok() });
+    }
+}
+
+#[async_trait]
+impl TUNetCommand for Exporter {
+    async fn run(&self) -> Result<()> {
+        let metrics = Metrics::new(
+            self.host.or_else(read_host),
+            read_cred()?,
+            self.interval,
+            None,
+        )?;
+        listen(self.listen, metrics).await
+    }
+}
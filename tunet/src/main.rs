@@ -1,16 +1,94 @@
 #![forbid(unsafe_code)]
 
 mod commands;
+mod config;
+#[cfg(feature = "daemon")]
+mod daemon;
+mod debug;
+#[cfg(feature = "usereg")]
+mod exporter;
+#[cfg(feature = "usereg")]
+mod usereg;
 
 use clap::Parser;
 use commands::{TUNet, TUNetCommand};
+use std::net::IpAddr;
+use std::path::PathBuf;
 use tokio::runtime::Builder as RuntimeBuilder;
-use tunet_helper::Result;
+#[cfg(feature = "fixtures")]
+use tunet_helper::Fixtures;
+use tunet_helper::{HttpClientOptions, Result};
+#[cfg(feature = "fixtures")]
+use tunet_settings_cli::set_fixtures;
+use tunet_settings_cli::{init_logging, read_password_stdin, set_http_options, FileSettingsReader};
+
+#[derive(Debug, Parser)]
+#[clap(about, version, author)]
+struct Opt {
+    #[clap(long, global = true)]
+    /// 使用的配置名称
+    profile: Option<String>,
+    #[clap(long, global = true, conflicts_with = "profile")]
+    /// 使用的设置文件路径，不能与 --profile 同时使用
+    config: Option<PathBuf>,
+    #[clap(long, global = true)]
+    /// 从标准输入读取密码
+    password_stdin: bool,
+    #[clap(long, global = true)]
+    /// 连接超时（秒）
+    connect_timeout: Option<u64>,
+    #[clap(long, global = true)]
+    /// 请求超时（秒）
+    timeout: Option<u64>,
+    #[clap(long, global = true)]
+    /// 访问 usereg 时使用的 HTTP 或 SOCKS 代理
+    proxy: Option<String>,
+    #[clap(long, global = true)]
+    /// 绑定的本地地址
+    local_address: Option<IpAddr>,
+    #[clap(long, global = true)]
+    /// 自定义 User-Agent
+    user_agent: Option<String>,
+    #[cfg(feature = "fixtures")]
+    #[clap(long, global = true, conflicts_with = "replay")]
+    /// 将请求与响应记录到夹具文件，凭据会被隐去
+    record: Option<PathBuf>,
+    #[cfg(feature = "fixtures")]
+    #[clap(long, global = true)]
+    /// 从夹具文件回放响应，不访问网络
+    replay: Option<PathBuf>,
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    /// 输出更详细的日志，可重复使用
+    verbose: u8,
+    #[clap(long, global = true)]
+    /// 日志文件路径
+    log_file: Option<PathBuf>,
+    #[clap(subcommand)]
+    command: TUNet,
+}
 
 fn main() -> Result<()> {
-    let opt = TUNet::parse();
+    let opt = Opt::parse();
+    init_logging(opt.verbose, opt.log_file.as_deref())?;
+    FileSettingsReader::set_default(opt.profile.clone(), opt.config.clone());
+    if opt.password_stdin {
+        read_password_stdin()?;
+    }
+    set_http_options(HttpClientOptions {
+        connect_timeout: opt.connect_timeout,
+        timeout: opt.timeout,
+        proxy: opt.proxy.clone(),
+        local_address: opt.local_address,
+        user_agent: opt.user_agent.clone(),
+    });
+    #[cfg(feature = "fixtures")]
+    set_fixtures(match (&opt.record, &opt.replay) {
+        (Some(path), _) => Some(Fixtures::record(path)),
+        (None, Some(path)) => Some(Fixtures::replay(path)?),
+        (None, None) => None,
+    });
     RuntimeBuilder::new_current_thread()
         .enable_all()
         .build()?
-        .block_on(opt.run())
+        .block_on(opt.command.run())
 }
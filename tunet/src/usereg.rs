@@ -0,0 +1,223 @@
+use crate::commands::{color_choice, get_flux_color, TUNetCommand};
+use async_trait::async_trait;
+use clap::Parser;
+use futures_util::{pin_mut, stream::TryStreamExt};
+use itertools::Itertools;
+use mac_address::MacAddressIterator;
+use std::cmp::Reverse;
+use std::net::Ipv4Addr;
+use termcolor::{Color, StandardStream};
+use termcolor_output as tco;
+use tunet_helper::{usereg::*, *};
+use tunet_settings_cli::*;
+
+#[derive(Debug, Parser)]
+pub struct Online {}
+
+#[async_trait]
+impl TUNetCommand for Online {
+    async fn run(&self) -> Result<()> {
+        let client = http_client()?;
+        let cred = read_cred()?;
+        let c = UseregHelper::new(cred, client)
+            .with_retry(read_retry())
+            .with_fixtures(read_fixtures());
+        c.login().await?;
+        let us = c.users();
+        let mac_addrs = MacAddressIterator::new()
+            .map(|it| it.collect::<Vec<_>>())
+            .unwrap_or_default();
+        let stdout = StandardStream::stdout(color_choice());
+        let mut stdout = tco::ResetGuard::Owned(stdout);
+        tco::writeln!(
+            stdout,
+            "    IP地址            登录时间         流量        MAC地址"
+        )?;
+
+        pin_mut!(us);
+        while let Some(u) = us.try_next().await? {
+            let is_self = mac_addrs
+                .iter()
+                .any(|it| Some(it) == u.mac_address.as_ref());
+            tco::writeln!(
+                stdout,
+                "{}{:15} {}{:20} {}{:>8} {}{} {}{}",
+                fg!(Some(Color::Yellow)),
+                u.address,
+                fg!(Some(Color::Green)),
+                u.login_time.naive_local(),
+                fg!(Some(get_flux_color(&u.flux, true))),
+                u.flux,
+                fg!(Some(Color::Cyan)),
+                u.mac_address.map(|a| a.to_string()).unwrap_or_default(),
+                fg!(Some(Color::Magenta)),
+                if is_self { "本机" } else { "" }
+            )?;
+        }
+        save_cred(c.cred()).await
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct UseregConnect {
+    #[clap(long, short)]
+    /// IP地址
+    address: Ipv4Addr,
+}
+
+#[async_trait]
+impl TUNetCommand for UseregConnect {
+    async fn run(&self) -> Result<()> {
+        let client = http_client()?;
+        let cred = read_cred()?;
+        let c = UseregHelper::new(cred, client)
+            .with_retry(read_retry())
+            .with_fixtures(read_fixtures());
+        c.login().await?;
+        let res = c.connect(self.address).await?;
+        println!("{}", res);
+        save_cred(c.cred()).await
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct UseregDrop {
+    #[clap(long, short)]
+    /// IP地址
+    address: Ipv4Addr,
+}
+
+#[async_trait]
+impl TUNetCommand for UseregDrop {
+    async fn run(&self) -> Result<()> {
+        let client = http_client()?;
+        let cred = read_cred()?;
+        let c = UseregHelper::new(cred, client)
+            .with_retry(read_retry())
+            .with_fixtures(read_fixtures());
+        c.login().await?;
+        let res = c.drop(self.address).await?;
+        println!("{}", res);
+        save_cred(c.cred()).await
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct Detail {
+    #[clap(long, short, default_value = "logout")]
+    /// 排序方式
+    order: NetDetailOrder,
+    #[clap(long, short)]
+    /// 倒序
+    descending: bool,
+    #[clap(long, short)]
+    /// 按日期分组
+    grouping: bool,
+}
+
+impl Detail {
+    async fn run_detail(&self) -> Result<()> {
+        let client = http_client()?;
+        let cred = read_cred()?;
+        let c = UseregHelper::new(cred, client)
+            .with_retry(read_retry())
+            .with_fixtures(read_fixtures());
+        c.login().await?;
+        let details = c.details(self.order, self.descending);
+        let stdout = StandardStream::stdout(color_choice());
+        let mut stdout = tco::ResetGuard::Owned(stdout);
+        tco::writeln!(stdout, "      登录时间             注销时间         流量")?;
+        let mut total_flux = Flux(0);
+
+        pin_mut!(details);
+        while let Some(d) = details.try_next().await? {
+            tco::writeln!(
+                stdout,
+                "{}{:20} {:20} {}{:>8}",
+                fg!(Some(Color::Green)),
+                d.login_time.naive_local(),
+                d.logout_time.naive_local(),
+                fg!(Some(get_flux_color(&d.flux, false))),
+                d.flux
+            )?;
+            total_flux.0 += d.flux.0;
+        }
+        tco::writeln!(
+            stdout,
+            "{}总流量 {}{}{}",
+            fg!(Some(Color::Cyan)),
+            fg!(Some(get_flux_color(&total_flux, true))),
+            bold!(true),
+            total_flux
+        )?;
+        save_cred(c.cred()).await
+    }
+
+    async fn run_detail_grouping(&self) -> Result<()> {
+        let client = http_client()?;
+        let cred = read_cred()?;
+        let c = UseregHelper::new(cred, client)
+            .with_retry(read_retry())
+            .with_fixtures(read_fixtures());
+        c.login().await?;
+        let details = c
+            .details(NetDetailOrder::LogoutTime, self.descending)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mut details = details
+            .into_iter()
+            .group_by(|detail| detail.logout_time.naive_local().date())
+            .into_iter()
+            .map(|(key, group)| (key, Flux(group.map(|detail| detail.flux.0).sum::<u64>())))
+            .collect::<Vec<_>>();
+        match self.order {
+            NetDetailOrder::Flux => {
+                if self.descending {
+                    details.sort_unstable_by_key(|(_, flux)| Reverse(*flux));
+                } else {
+                    details.sort_unstable_by_key(|(_, flux)| *flux);
+                }
+            }
+            _ => {
+                if self.descending {
+                    details.sort_unstable_by_key(|(date, _)| Reverse(date.day()));
+                }
+            }
+        }
+        let stdout = StandardStream::stdout(color_choice());
+        let mut stdout = tco::ResetGuard::Owned(stdout);
+        tco::writeln!(stdout, " 登录日期    流量")?;
+        let mut total_flux = Flux(0);
+        for (date, flux) in details {
+            tco::writeln!(
+                stdout,
+                "{}{:10} {}{:>8}",
+                fg!(Some(Color::Green)),
+                date,
+                fg!(Some(get_flux_color(&flux, true))),
+                flux
+            )?;
+            total_flux.0 += flux.0;
+        }
+        tco::writeln!(
+            stdout,
+            "{}总流量 {}{}{}",
+            fg!(Some(Color::Cyan)),
+            fg!(Some(get_flux_color(&total_flux, true))),
+            bold!(true),
+            total_flux
+        )?;
+        save_cred(c.cred()).await
+    }
+}
+
+#[async_trait]
+impl TUNetCommand for Detail {
+    async fn run(&self) -> Result<()> {
+        if self.grouping {
+            self.run_detail_grouping().await
+        } else {
+            self.run_detail().await
+        }
+    }
+}